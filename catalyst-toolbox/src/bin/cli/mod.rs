@@ -53,7 +53,7 @@ pub enum CatalystCommand {
     VoteCheck(vote_check::VoteCheck),
     /// Prints voting statistics
     Stats(stats::Stats),
-    /// Process raw registrations to produce initial blockchain setup
+    /// Process raw registrations to produce initial blockchain setup and inspect snapshots
     Snapshot(snapshot::SnapshotCmd),
 }
 
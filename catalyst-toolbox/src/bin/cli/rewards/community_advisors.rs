@@ -19,33 +19,43 @@ use catalyst_toolbox::community_advisors::models::{
 use catalyst_toolbox::utils::csv::dump_data_to_csv;
 use structopt::StructOpt;
 
-#[derive(Debug, Deserialize, StructOpt)]
+#[derive(Debug, Serialize, Deserialize, StructOpt)]
 pub struct FundSettingOpt {
     /// % ratio, range in [0, 100]
     #[structopt(long = "rewards-ratio")]
-    proposal_ratio: u8,
+    pub(super) proposal_ratio: u8,
     /// % ratio, range in [0, 100]
     #[structopt(long = "bonus-ratio")]
-    bonus_ratio: u8,
+    pub(super) bonus_ratio: u8,
     /// total amount of funds to be rewarded (integer value)
     #[structopt(long = "funds")]
-    total: u64,
+    pub(super) total: u64,
 }
 
-#[derive(Debug, Deserialize, StructOpt)]
+impl FundSettingOpt {
+    /// Check that the proposal and bonus ratios add up to 100
+    pub fn check_ratios(&self) -> Result<(), Report> {
+        if self.bonus_ratio as u16 + self.proposal_ratio as u16 != 100 {
+            bail!("Wrong ratios: bonus + proposal ratios should be 100");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, StructOpt)]
 pub struct ProposalRewardsSlotsOpt {
     /// excellent reviews amount of rewards tickets
     #[structopt(long)]
-    excellent_slots: u64,
+    pub(super) excellent_slots: u64,
     /// good reviews amount of rewards tickets
     #[structopt(long)]
-    good_slots: u64,
+    pub(super) good_slots: u64,
     /// maximum number of excellent reviews being rewarded per proposal
     #[structopt(long)]
-    max_excellent_reviews: u64,
+    pub(super) max_excellent_reviews: u64,
     /// maximum number of good reviews being rewarded per proposal
     #[structopt(long)]
-    max_good_reviews: u64,
+    pub(super) max_good_reviews: u64,
 }
 
 #[derive(Debug, Deserialize, StructOpt)]
@@ -107,9 +117,7 @@ pub fn ca_rewards(
     seed: String,
     proposal_bonus_output: Option<PathBuf>,
 ) -> Result<(), Report> {
-    if fund_settings.bonus_ratio + fund_settings.proposal_ratio != 100 {
-        bail!("Wrong ratios: bonus + proposal ratios should be 100");
-    }
+    fund_settings.check_ratios()?;
 
     let proposal_reviews = read_proposal_reviews(&assessments_path)?;
     let approved_proposals = read_approved_proposals(&approved_proposals_path)?;
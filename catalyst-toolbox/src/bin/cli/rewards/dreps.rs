@@ -1,14 +1,20 @@
+use super::rounding::RoundingArgs;
+use catalyst_toolbox::rewards::dreps::calc_dreps_rewards;
+use catalyst_toolbox::rewards::rounding::DustReport;
 use catalyst_toolbox::rewards::voters::calc_voter_rewards;
-use catalyst_toolbox::rewards::{Rewards, Threshold};
-use color_eyre::Report;
+use catalyst_toolbox::rewards::{Rewards, Threshold, VoteCount};
+use color_eyre::{eyre::eyre, Report};
+use jcli_lib::block::open_output;
 use jcli_lib::jcli_lib::block::Common;
 use jormungandr_lib::{crypto::account::Identifier, interfaces::AccountVotes};
-use snapshot_lib::{registration::MainnetRewardAddress, SnapshotInfo};
+use snapshot_lib::{
+    registration::MainnetRewardAddress, reward_address::AddressEncoding, SnapshotInfo, VotingGroup,
+};
 use structopt::StructOpt;
 use vit_servicing_station_lib::db::models::proposals::FullProposalInfo;
 
 use std::collections::{BTreeMap, HashMap};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(StructOpt)]
 #[structopt(rename_all = "kebab-case")]
@@ -43,19 +49,36 @@ pub struct DrepsRewards {
     /// Can be obtained from /api/v0/proposals.
     #[structopt(long)]
     proposals: PathBuf,
+
+    /// Encoding of reward addresses in the output, either bech32 or hex
+    #[structopt(long, default_value = "bech32")]
+    address_encoding: AddressEncoding,
+
+    #[structopt(flatten)]
+    rounding: RoundingArgs,
 }
 
 fn write_rewards_results(
-    common: Common,
-    rewards: &BTreeMap<MainnetRewardAddress, Rewards>,
+    output: &Option<PathBuf>,
+    rewards: &BTreeMap<MainnetRewardAddress, u64>,
+    address_encoding: AddressEncoding,
 ) -> Result<(), Report> {
-    let writer = common.open_output()?;
+    // check all addresses before writing anything
+    let records = rewards
+        .iter()
+        .map(|(address, rewards)| {
+            Ok([
+                super::voters::encode_address(address, address_encoding)?,
+                rewards.to_string(),
+            ])
+        })
+        .collect::<Result<Vec<_>, Report>>()?;
+
+    let writer = open_output(output)?;
     let header = ["Address", "Reward for the voter (lovelace)"];
     let mut csv_writer = csv::Writer::from_writer(writer);
     csv_writer.write_record(&header)?;
-
-    for (address, rewards) in rewards.iter() {
-        let record = [address.to_string(), rewards.trunc().to_string()];
+    for record in records {
         csv_writer.write_record(&record)?;
     }
 
@@ -72,6 +95,8 @@ impl DrepsRewards {
             vote_threshold,
             per_challenge_threshold,
             proposals,
+            address_encoding,
+            rounding,
         } = self;
 
         let proposals = serde_json::from_reader::<_, Vec<FullProposalInfo>>(
@@ -109,7 +134,82 @@ impl DrepsRewards {
             Rewards::from(total_rewards),
         )?;
 
-        write_rewards_results(common, &results)?;
+        let (results, _) = rounding.round(&results, total_rewards)?;
+        write_rewards_results(&common.output_file, &results, address_encoding)?;
         Ok(())
     }
 }
+
+/// Calculate the rewards of the top `top_dreps_to_reward` representatives in `voting_group`,
+/// proportionally to their voting power, and write them per reward address in the same
+/// format as `rewards dreps`.
+///
+/// Each representative is paid to the reward address contributing the most voting power to
+/// its voting key in the snapshot.
+#[allow(clippy::too_many_arguments)]
+pub fn drep_rewards(
+    output: &Path,
+    votes_count_path: &Path,
+    snapshot_path: &Path,
+    voting_group: VotingGroup,
+    top_dreps_to_reward: usize,
+    vote_threshold: u64,
+    total_rewards: u64,
+    address_encoding: AddressEncoding,
+    rounding: &RoundingArgs,
+) -> Result<DustReport, Report> {
+    let vote_count: VoteCount = serde_json::from_reader(jcli_lib::utils::io::open_file_read(
+        &Some(votes_count_path),
+    )?)?;
+
+    let snapshot: Vec<SnapshotInfo> =
+        serde_json::from_reader(jcli_lib::utils::io::open_file_read(&Some(snapshot_path))?)?;
+    let addresses = drep_reward_addresses(&snapshot, &voting_group);
+
+    let results = calc_dreps_rewards(
+        snapshot,
+        vote_count,
+        voting_group,
+        top_dreps_to_reward,
+        Threshold::new(
+            vote_threshold.try_into()?,
+            Default::default(),
+            Default::default(),
+        )?,
+        Rewards::from(total_rewards),
+    )?;
+
+    let mut rewards = BTreeMap::<MainnetRewardAddress, Rewards>::new();
+    for (voting_key, reward) in results {
+        let address = addresses
+            .get(&voting_key)
+            .ok_or_else(|| eyre!("no reward address for drep {}", voting_key.to_hex()))?;
+        *rewards.entry(address.clone()).or_default() += reward;
+    }
+
+    let (rewards, dust) = rounding.round(&rewards, total_rewards)?;
+    write_rewards_results(&Some(output.to_path_buf()), &rewards, address_encoding)?;
+    Ok(dust)
+}
+
+/// Reward address of each representative in `voting_group`, the one contributing the most
+/// voting power to its voting key, or the greatest of them in case of a tie
+fn drep_reward_addresses(
+    snapshot: &[SnapshotInfo],
+    voting_group: &str,
+) -> HashMap<Identifier, MainnetRewardAddress> {
+    snapshot
+        .iter()
+        .filter(|info| info.hir.voting_group == voting_group)
+        .filter_map(|info| {
+            let mut per_address = BTreeMap::<_, u64>::new();
+            for contribution in &info.contributions {
+                *per_address.entry(&contribution.reward_address).or_default() += contribution.value;
+            }
+            per_address
+                .into_iter()
+                .max_by_key(|(_, value)| *value)
+                .map(|(address, _)| (info.hir.voting_key.clone(), address.clone()))
+        })
+        .collect()
+}
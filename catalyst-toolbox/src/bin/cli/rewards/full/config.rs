@@ -1,18 +1,23 @@
 use std::path::PathBuf;
 
+use catalyst_toolbox::rewards::rounding::Rounding;
+use color_eyre::{eyre::eyre, Report};
 use rust_decimal::Decimal;
-use serde::Deserialize;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+use snapshot_lib::VotingGroup;
 
 use crate::cli::rewards::community_advisors::{FundSettingOpt, ProposalRewardsSlotsOpt};
+use crate::cli::rewards::veterans::is_descending;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug)]
 pub(super) struct Config {
     pub(super) inputs: Inputs,
     pub(super) outputs: Outputs,
     pub(super) params: Params,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug)]
 pub(super) struct Inputs {
     pub(super) block_file: PathBuf,
     pub(super) snapshot_path: PathBuf,
@@ -26,44 +31,58 @@ pub(super) struct Inputs {
     pub(super) proposals_path: PathBuf,
     pub(super) committee_keys: PathBuf,
     pub(super) excluded_proposals: Option<PathBuf>,
+    /// Per-challenge overrides of the proposer thresholds
+    pub(super) challenge_thresholds: Option<PathBuf>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug)]
 pub(super) struct Outputs {
     pub(super) voter_rewards_output: PathBuf,
     pub(super) veterans_rewards_output: PathBuf,
     pub(super) ca_rewards_output: PathBuf,
     pub(super) proposer_rewards_output: PathBuf,
+    /// Where to write the manifest of the run, `manifest.json` next to the config by default
+    pub(super) manifest_output: Option<PathBuf>,
+    /// Required when `drep_params` are set
+    pub(super) drep_rewards_output: Option<PathBuf>,
+    /// Where to write the json report of the amounts lost or redistributed by rounding
+    /// voter, drep and veteran rewards, not written by default
+    pub(super) dust_report_output: Option<PathBuf>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize)]
 pub(super) struct Params {
     pub(super) voter_params: VoterParams,
     pub(super) proposer_params: ProposerParams,
     pub(super) ca_params: CaParams,
     pub(super) vca_params: VcaParams,
+    /// Drep rewards are only calculated if set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) drep_params: Option<DrepParams>,
+    /// How voter, drep and veteran rewards are rounded to lovelace, `truncate` by default
+    pub(super) rounding: Rounding,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize)]
 pub(super) struct VoterParams {
     pub(super) total_rewards: u64,
     pub(super) vote_threshold: u64,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize)]
 pub(super) struct ProposerParams {
-    pub(super) stake_threshold: f64,
-    pub(super) approval_threshold: f64,
+    pub(super) stake_threshold: Decimal,
+    pub(super) approval_threshold: Decimal,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize)]
 pub(super) struct CaParams {
     pub(super) rewards_slots: ProposalRewardsSlotsOpt,
     pub(super) fund_settings: FundSettingOpt,
     pub(super) seed: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize)]
 pub(super) struct VcaParams {
     pub(super) total_rewards: u64,
     pub(super) rewards_agreement_rate_cutoffs: Vec<Decimal>,
@@ -74,3 +93,515 @@ pub(super) struct VcaParams {
     pub(super) max_rankings_reputation: usize,
     pub(super) max_rankings_rewards: usize,
 }
+
+#[derive(Debug, Serialize)]
+pub(super) struct DrepParams {
+    pub(super) voting_group: VotingGroup,
+    pub(super) top_dreps_to_reward: usize,
+    pub(super) vote_threshold: u64,
+    pub(super) total_rewards: u64,
+}
+
+impl Config {
+    /// Validate and parse a config, reporting all missing or inconsistent fields at once
+    pub(super) fn from_value(value: &Value) -> Result<Self, Report> {
+        let mut problems = Vec::new();
+
+        let inputs =
+            Inputs::from_section(&Section::new(value, "inputs", &mut problems), &mut problems);
+        let outputs_section = Section::new(value, "outputs", &mut problems);
+        let outputs = Outputs::from_section(&outputs_section, &mut problems);
+        let params_section = Section::new(value, "params", &mut problems);
+        let params = Params::from_section(&params_section, &mut problems);
+
+        if let (Some(outputs_fields), Some(params_fields)) =
+            (outputs_section.fields, params_section.fields)
+        {
+            match (
+                is_set(outputs_fields, "drep_rewards_output"),
+                is_set(params_fields, "drep_params"),
+            ) {
+                (false, true) => problems.push(
+                    "outputs.drep_rewards_output: required when params.drep_params is set"
+                        .to_string(),
+                ),
+                (true, false) => problems.push(
+                    "params.drep_params: required when outputs.drep_rewards_output is set"
+                        .to_string(),
+                ),
+                _ => {}
+            }
+        }
+
+        match (inputs, outputs, params) {
+            (Some(inputs), Some(outputs), Some(params)) if problems.is_empty() => Ok(Self {
+                inputs,
+                outputs,
+                params,
+            }),
+            _ => Err(eyre!("invalid config:\n  - {}", problems.join("\n  - "))),
+        }
+    }
+}
+
+impl Inputs {
+    fn from_section(section: &Section, problems: &mut Vec<String>) -> Option<Self> {
+        let block_file = section.field("block_file", problems);
+        let snapshot_path = section.field("snapshot_path", problems);
+        let vote_count_path = section.field("vote_count_path", problems);
+        let reviews_csv = section.field("reviews_csv", problems);
+        let assessments_path = section.field("assessments_path", problems);
+        let proposal_bonus_output = section.field("proposal_bonus_output", problems);
+        let approved_proposals_path = section.field("approved_proposals_path", problems);
+        let active_voteplans = section.field("active_voteplans", problems);
+        let challenges = section.field("challenges", problems);
+        let proposals_path = section.field("proposals_path", problems);
+        let committee_keys = section.field("committee_keys", problems);
+        let excluded_proposals = section.field("excluded_proposals", problems);
+        let challenge_thresholds = section.field("challenge_thresholds", problems);
+        Some(Self {
+            block_file: block_file?,
+            snapshot_path: snapshot_path?,
+            vote_count_path: vote_count_path?,
+            reviews_csv: reviews_csv?,
+            assessments_path: assessments_path?,
+            proposal_bonus_output: proposal_bonus_output?,
+            approved_proposals_path: approved_proposals_path?,
+            active_voteplans: active_voteplans?,
+            challenges: challenges?,
+            proposals_path: proposals_path?,
+            committee_keys: committee_keys?,
+            excluded_proposals: excluded_proposals?,
+            challenge_thresholds: challenge_thresholds?,
+        })
+    }
+}
+
+impl Outputs {
+    fn from_section(section: &Section, problems: &mut Vec<String>) -> Option<Self> {
+        let voter_rewards_output = section.field("voter_rewards_output", problems);
+        let veterans_rewards_output = section.field("veterans_rewards_output", problems);
+        let ca_rewards_output = section.field("ca_rewards_output", problems);
+        let proposer_rewards_output = section.field("proposer_rewards_output", problems);
+        let manifest_output = section.field("manifest_output", problems);
+        let drep_rewards_output = section.field("drep_rewards_output", problems);
+        let dust_report_output = section.field("dust_report_output", problems);
+        Some(Self {
+            voter_rewards_output: voter_rewards_output?,
+            veterans_rewards_output: veterans_rewards_output?,
+            ca_rewards_output: ca_rewards_output?,
+            proposer_rewards_output: proposer_rewards_output?,
+            manifest_output: manifest_output?,
+            drep_rewards_output: drep_rewards_output?,
+            dust_report_output: dust_report_output?,
+        })
+    }
+}
+
+impl Params {
+    fn from_section(section: &Section, problems: &mut Vec<String>) -> Option<Self> {
+        let voter_params =
+            VoterParams::from_section(&section.section("voter_params", problems), problems);
+        let proposer_params =
+            ProposerParams::from_section(&section.section("proposer_params", problems), problems);
+        let ca_params = CaParams::from_section(&section.section("ca_params", problems), problems);
+        let vca_params =
+            VcaParams::from_section(&section.section("vca_params", problems), problems);
+        let drep_params = match section.fields {
+            Some(fields) if is_set(fields, "drep_params") => {
+                DrepParams::from_section(&section.section("drep_params", problems), problems)
+                    .map(Some)
+            }
+            _ => Some(None),
+        };
+        let rounding: Option<Option<Rounding>> = section.field("rounding", problems);
+        Some(Self {
+            voter_params: voter_params?,
+            proposer_params: proposer_params?,
+            ca_params: ca_params?,
+            vca_params: vca_params?,
+            drep_params: drep_params?,
+            rounding: rounding?.unwrap_or_default(),
+        })
+    }
+}
+
+impl VoterParams {
+    fn from_section(section: &Section, problems: &mut Vec<String>) -> Option<Self> {
+        let total_rewards = section.field("total_rewards", problems);
+        let vote_threshold = section.field("vote_threshold", problems);
+        Some(Self {
+            total_rewards: total_rewards?,
+            vote_threshold: vote_threshold?,
+        })
+    }
+}
+
+impl ProposerParams {
+    fn from_section(section: &Section, problems: &mut Vec<String>) -> Option<Self> {
+        let stake_threshold: Option<Decimal> = section.field("stake_threshold", problems);
+        let approval_threshold: Option<Decimal> = section.field("approval_threshold", problems);
+        if let Some(stake_threshold) = &stake_threshold {
+            if !(Decimal::ZERO..=Decimal::ONE).contains(stake_threshold) {
+                problems.push(format!(
+                    "{}.stake_threshold: expected a value between 0 and 1",
+                    section.name
+                ));
+            }
+        }
+        if let Some(approval_threshold) = &approval_threshold {
+            if approval_threshold.is_sign_negative() {
+                problems.push(format!(
+                    "{}.approval_threshold: expected a positive value",
+                    section.name
+                ));
+            }
+        }
+        Some(Self {
+            stake_threshold: stake_threshold?,
+            approval_threshold: approval_threshold?,
+        })
+    }
+}
+
+impl CaParams {
+    fn from_section(section: &Section, problems: &mut Vec<String>) -> Option<Self> {
+        let rewards_slots = section.section("rewards_slots", problems);
+        let excellent_slots = rewards_slots.field("excellent_slots", problems);
+        let good_slots = rewards_slots.field("good_slots", problems);
+        let max_excellent_reviews = rewards_slots.field("max_excellent_reviews", problems);
+        let max_good_reviews = rewards_slots.field("max_good_reviews", problems);
+
+        let fund_settings = section.section("fund_settings", problems);
+        let proposal_ratio = fund_settings.field("proposal_ratio", problems);
+        let bonus_ratio = fund_settings.field("bonus_ratio", problems);
+        let total = fund_settings.field("total", problems);
+        let fund_settings_name = fund_settings.name;
+        let fund_settings = match (proposal_ratio, bonus_ratio, total) {
+            (Some(proposal_ratio), Some(bonus_ratio), Some(total)) => {
+                let fund_settings = FundSettingOpt {
+                    proposal_ratio,
+                    bonus_ratio,
+                    total,
+                };
+                if let Err(e) = fund_settings.check_ratios() {
+                    problems.push(format!("{}: {}", fund_settings_name, e));
+                }
+                Some(fund_settings)
+            }
+            _ => None,
+        };
+
+        let seed = section.field("seed", problems);
+        Some(Self {
+            rewards_slots: ProposalRewardsSlotsOpt {
+                excellent_slots: excellent_slots?,
+                good_slots: good_slots?,
+                max_excellent_reviews: max_excellent_reviews?,
+                max_good_reviews: max_good_reviews?,
+            },
+            fund_settings: fund_settings?,
+            seed: seed?,
+        })
+    }
+}
+
+impl VcaParams {
+    fn from_section(section: &Section, problems: &mut Vec<String>) -> Option<Self> {
+        let total_rewards = section.field("total_rewards", problems);
+        let rewards_agreement_rate_cutoffs: Option<Vec<Decimal>> =
+            section.field("rewards_agreement_rate_cutoffs", problems);
+        let rewards_agreement_rate_modifiers: Option<Vec<Decimal>> =
+            section.field("rewards_agreement_rate_modifiers", problems);
+        let reputation_agreement_rate_cutoffs: Option<Vec<Decimal>> =
+            section.field("reputation_agreement_rate_cutoffs", problems);
+        let reputation_agreement_rate_modifiers: Option<Vec<Decimal>> =
+            section.field("reputation_agreement_rate_modifiers", problems);
+        let min_rankings: Option<usize> = section.field("min_rankings", problems);
+        let max_rankings_reputation: Option<usize> =
+            section.field("max_rankings_reputation", problems);
+        let max_rankings_rewards: Option<usize> = section.field("max_rankings_rewards", problems);
+
+        for (cutoffs_name, cutoffs, modifiers_name, modifiers) in [
+            (
+                "rewards_agreement_rate_cutoffs",
+                &rewards_agreement_rate_cutoffs,
+                "rewards_agreement_rate_modifiers",
+                &rewards_agreement_rate_modifiers,
+            ),
+            (
+                "reputation_agreement_rate_cutoffs",
+                &reputation_agreement_rate_cutoffs,
+                "reputation_agreement_rate_modifiers",
+                &reputation_agreement_rate_modifiers,
+            ),
+        ] {
+            if let (Some(cutoffs), Some(modifiers)) = (cutoffs, modifiers) {
+                if cutoffs.len() != modifiers.len() {
+                    problems.push(format!(
+                        "{}: expected the same number of {} and {}",
+                        section.name, cutoffs_name, modifiers_name
+                    ));
+                }
+            }
+            if let Some(cutoffs) = cutoffs {
+                if !is_descending(cutoffs) {
+                    problems.push(format!(
+                        "{}.{}: expected descending values",
+                        section.name, cutoffs_name
+                    ));
+                }
+            }
+        }
+        if let (Some(min_rankings), Some(max_rankings_reputation), Some(max_rankings_rewards)) =
+            (min_rankings, max_rankings_reputation, max_rankings_rewards)
+        {
+            if min_rankings > max_rankings_reputation || min_rankings > max_rankings_rewards {
+                problems.push(format!(
+                    "{}.min_rankings: expected to be at most the maximum rankings",
+                    section.name
+                ));
+            }
+        }
+
+        Some(Self {
+            total_rewards: total_rewards?,
+            rewards_agreement_rate_cutoffs: rewards_agreement_rate_cutoffs?,
+            rewards_agreement_rate_modifiers: rewards_agreement_rate_modifiers?,
+            reputation_agreement_rate_cutoffs: reputation_agreement_rate_cutoffs?,
+            reputation_agreement_rate_modifiers: reputation_agreement_rate_modifiers?,
+            min_rankings: min_rankings?,
+            max_rankings_reputation: max_rankings_reputation?,
+            max_rankings_rewards: max_rankings_rewards?,
+        })
+    }
+}
+
+impl DrepParams {
+    fn from_section(section: &Section, problems: &mut Vec<String>) -> Option<Self> {
+        let voting_group = section.field("voting_group", problems);
+        let top_dreps_to_reward = section.field("top_dreps_to_reward", problems);
+        let vote_threshold = section.field("vote_threshold", problems);
+        let total_rewards = section.field("total_rewards", problems);
+        Some(Self {
+            voting_group: voting_group?,
+            top_dreps_to_reward: top_dreps_to_reward?,
+            vote_threshold: vote_threshold?,
+            total_rewards: total_rewards?,
+        })
+    }
+}
+
+/// Section of the config, whose fields are deserialized one at a time so that the problems
+/// of all of them are reported instead of stopping at the first one
+struct Section<'a> {
+    name: String,
+    /// `None` if the section is missing or is not an object, which is already reported
+    fields: Option<&'a Map<String, Value>>,
+}
+
+impl<'a> Section<'a> {
+    fn new(config: &'a Value, name: &str, problems: &mut Vec<String>) -> Self {
+        Self::from_value(name.to_string(), config.get(name), problems)
+    }
+
+    fn from_value(name: String, value: Option<&'a Value>, problems: &mut Vec<String>) -> Self {
+        let fields = match value {
+            Some(Value::Object(fields)) => Some(fields),
+            None | Some(Value::Null) => {
+                problems.push(format!("{}: missing section", name));
+                None
+            }
+            Some(_) => {
+                problems.push(format!("{}: expected an object", name));
+                None
+            }
+        };
+        Self { name, fields }
+    }
+
+    /// Section nested in `field`
+    fn section(&self, field: &str, problems: &mut Vec<String>) -> Section<'a> {
+        let name = format!("{}.{}", self.name, field);
+        match self.fields {
+            Some(fields) => Self::from_value(name, fields.get(field), problems),
+            None => Section { name, fields: None },
+        }
+    }
+
+    /// Deserialize `field`, which can only be left out if its type accepts `null`
+    fn field<T: DeserializeOwned>(&self, field: &str, problems: &mut Vec<String>) -> Option<T> {
+        let value = match self.fields?.get(field) {
+            None | Some(Value::Null) => {
+                return serde_json::from_value(Value::Null)
+                    .map_err(|_| problems.push(format!("{}.{}: missing field", self.name, field)))
+                    .ok()
+            }
+            Some(value) => value,
+        };
+        serde_json::from_value(value.clone())
+            .map_err(|e| problems.push(format!("{}.{}: {}", self.name, field, e)))
+            .ok()
+    }
+}
+
+fn is_set(fields: &Map<String, Value>, field: &str) -> bool {
+    !matches!(fields.get(field), None | Some(Value::Null))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config() -> Value {
+        json!({
+            "inputs": {
+                "block_file": "block0.bin",
+                "snapshot_path": "snapshot.json",
+                "vote_count_path": "vote_count.json",
+                "reviews_csv": "reviews.csv",
+                "assessments_path": "assessments.csv",
+                "approved_proposals_path": "approved_proposals.csv",
+                "active_voteplans": "active_voteplans.json",
+                "challenges": "challenges.json",
+                "proposals_path": "proposals.json",
+                "committee_keys": "committee_keys.json",
+            },
+            "outputs": {
+                "voter_rewards_output": "voters.csv",
+                "veterans_rewards_output": "veterans.csv",
+                "ca_rewards_output": "ca.csv",
+                "proposer_rewards_output": "proposers.csv",
+            },
+            "params": {
+                "voter_params": { "total_rewards": 1000, "vote_threshold": 1 },
+                "proposer_params": { "stake_threshold": "0.01", "approval_threshold": "1.15" },
+                "ca_params": {
+                    "rewards_slots": {
+                        "excellent_slots": 12,
+                        "good_slots": 4,
+                        "max_excellent_reviews": 1,
+                        "max_good_reviews": 3,
+                    },
+                    "fund_settings": { "proposal_ratio": 80, "bonus_ratio": 20, "total": 1000 },
+                    "seed": "seed",
+                },
+                "vca_params": {
+                    "total_rewards": 1000,
+                    "rewards_agreement_rate_cutoffs": ["0.9", "0.8"],
+                    "rewards_agreement_rate_modifiers": ["1", "0.5"],
+                    "reputation_agreement_rate_cutoffs": ["0.7"],
+                    "reputation_agreement_rate_modifiers": ["1"],
+                    "min_rankings": 5,
+                    "max_rankings_reputation": 100,
+                    "max_rankings_rewards": 100,
+                },
+            },
+        })
+    }
+
+    #[test]
+    fn test_valid_config() {
+        let config = Config::from_value(&config()).unwrap();
+        assert!(config.params.drep_params.is_none());
+        assert_eq!(config.params.rounding, Rounding::Truncate);
+
+        let mut value = config();
+        value["outputs"]["drep_rewards_output"] = json!("dreps.csv");
+        value["params"]["drep_params"] = json!({
+            "voting_group": "rep",
+            "top_dreps_to_reward": 100,
+            "vote_threshold": 1,
+            "total_rewards": 1000,
+        });
+        value["outputs"]["dust_report_output"] = json!("dust.json");
+        value["params"]["rounding"] = json!("largest-remainder");
+        let config = Config::from_value(&value).unwrap();
+        assert_eq!(config.params.drep_params.unwrap().top_dreps_to_reward, 100);
+        assert_eq!(config.params.rounding, Rounding::LargestRemainder);
+        assert!(config.outputs.dust_report_output.is_some());
+    }
+
+    #[test]
+    fn test_approval_threshold_above_one_is_accepted() {
+        let config = Config::from_value(&config()).unwrap();
+        assert_eq!(
+            config.params.proposer_params.approval_threshold,
+            Decimal::new(115, 2)
+        );
+
+        let mut value = config();
+        value["params"]["proposer_params"]["approval_threshold"] = json!("-0.1");
+        let error = Config::from_value(&value).unwrap_err().to_string();
+        assert!(error.contains("params.proposer_params.approval_threshold"));
+    }
+
+    fn problems(value: &Value) -> Vec<String> {
+        let error = Config::from_value(value).unwrap_err().to_string();
+        error
+            .lines()
+            .skip(1)
+            .map(|line| line.trim_start_matches("  - ").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_all_problems_are_reported() {
+        let mut value = config();
+        value["inputs"]
+            .as_object_mut()
+            .unwrap()
+            .remove("block_file");
+        value["inputs"]
+            .as_object_mut()
+            .unwrap()
+            .remove("challenges");
+        value["params"]["voter_params"]["total_rewards"] = json!("all");
+        value["params"]["ca_params"]["fund_settings"]["bonus_ratio"] = json!(30);
+        value["params"]["ca_params"]["rewards_slots"]
+            .as_object_mut()
+            .unwrap()
+            .remove("good_slots");
+        value["params"]["vca_params"]["reputation_agreement_rate_modifiers"] = json!([]);
+        value["params"]["vca_params"]["rewards_agreement_rate_cutoffs"] = json!(["0.8", "0.9"]);
+        value["params"]["vca_params"]
+            .as_object_mut()
+            .unwrap()
+            .remove("max_rankings_rewards");
+        value["params"]["drep_params"] = json!({ "voting_group": "rep" });
+        value["params"]["rounding"] = json!("round");
+
+        assert_eq!(
+            problems(&value),
+            vec![
+                "inputs.block_file: missing field",
+                "inputs.challenges: missing field",
+                "params.voter_params.total_rewards: invalid type: string \"all\", expected u64",
+                "params.ca_params.rewards_slots.good_slots: missing field",
+                "params.ca_params.fund_settings: Wrong ratios: bonus + proposal ratios should be 100",
+                "params.vca_params.max_rankings_rewards: missing field",
+                "params.vca_params.rewards_agreement_rate_cutoffs: expected descending values",
+                "params.vca_params: expected the same number of reputation_agreement_rate_cutoffs and reputation_agreement_rate_modifiers",
+                "params.drep_params.top_dreps_to_reward: missing field",
+                "params.drep_params.vote_threshold: missing field",
+                "params.drep_params.total_rewards: missing field",
+                "params.rounding: unknown variant `round`, expected `truncate` or `largest-remainder`",
+                "outputs.drep_rewards_output: required when params.drep_params is set",
+            ]
+        );
+
+        let mut value = config();
+        value["outputs"]["drep_rewards_output"] = json!("dreps.csv");
+        value.as_object_mut().unwrap().remove("inputs");
+        value["params"]["proposer_params"] = json!(1);
+        assert_eq!(
+            problems(&value),
+            vec![
+                "inputs: missing section",
+                "params.proposer_params: expected an object",
+                "params.drep_params: required when outputs.drep_rewards_output is set",
+            ]
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Record of a run of `rewards full`, allowing a third party to reproduce and verify it.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use catalyst_toolbox::{
+    rewards::proposers::{build_path_for_challenge, io::json_from_file, FundedStatus},
+    utils::csv::load_data_from_csv,
+};
+use chain_crypto::hash::Blake2b256;
+use color_eyre::{eyre::eyre, Result};
+use serde::Serialize;
+use vit_servicing_station_lib::db::models::challenges::Challenge;
+
+use super::config::{Inputs, Outputs, Params};
+use crate::cli::rewards::outputs::{load_address_rewards, AdvisorRewards, ProposerResult};
+
+#[derive(Debug, Serialize)]
+pub(super) struct FileHash {
+    path: PathBuf,
+    blake2b256: String,
+}
+
+impl FileHash {
+    fn new(path: &Path) -> Result<Self> {
+        let contents =
+            std::fs::read(path).map_err(|e| eyre!("cannot read {}: {}", path.display(), e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            blake2b256: hex::encode(Blake2b256::new(&contents).as_ref()),
+        })
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub(super) struct CategoryTotal {
+    /// Sum of the rewards of the category, in the unit of its output
+    amount: u64,
+    /// Number of distinct recipients in the category: reward addresses for voters and dreps,
+    /// advisor ids for advisors and proposal ids for proposers
+    recipients: usize,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub(super) struct Totals {
+    voters: CategoryTotal,
+    /// Only set if drep rewards were calculated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dreps: Option<CategoryTotal>,
+    veterans: CategoryTotal,
+    community_advisors: CategoryTotal,
+    /// Requested funds of the funded proposals
+    proposers: CategoryTotal,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct Manifest {
+    /// Version of the toolbox which produced the results
+    version: &'static str,
+    config: FileHash,
+    inputs: BTreeMap<&'static str, FileHash>,
+    params: serde_json::Value,
+    outputs: Vec<FileHash>,
+    totals: Totals,
+    /// Sum of the recipients of each category. Recipients are identified differently in each
+    /// category, so a payee rewarded in several categories is counted once for each of them.
+    category_recipients: usize,
+}
+
+impl Manifest {
+    /// Hash the config and the inputs, before any output is written
+    pub(super) fn new(config: &Path, inputs: &Inputs, params: &Params) -> Result<Self> {
+        let mut paths = vec![
+            ("block_file", &inputs.block_file),
+            ("snapshot_path", &inputs.snapshot_path),
+            ("vote_count_path", &inputs.vote_count_path),
+            ("reviews_csv", &inputs.reviews_csv),
+            ("assessments_path", &inputs.assessments_path),
+            ("approved_proposals_path", &inputs.approved_proposals_path),
+            ("active_voteplans", &inputs.active_voteplans),
+            ("challenges", &inputs.challenges),
+            ("proposals_path", &inputs.proposals_path),
+            ("committee_keys", &inputs.committee_keys),
+        ];
+        if let Some(excluded_proposals) = &inputs.excluded_proposals {
+            paths.push(("excluded_proposals", excluded_proposals));
+        }
+        if let Some(challenge_thresholds) = &inputs.challenge_thresholds {
+            paths.push(("challenge_thresholds", challenge_thresholds));
+        }
+
+        Ok(Self {
+            version: env!("FULL_VERSION"),
+            config: FileHash::new(config)?,
+            inputs: paths
+                .into_iter()
+                .map(|(name, path)| Ok((name, FileHash::new(path)?)))
+                .collect::<Result<_>>()?,
+            params: serde_json::to_value(params)?,
+            outputs: Vec::new(),
+            totals: Totals::default(),
+            category_recipients: 0,
+        })
+    }
+
+    /// Hash the outputs and compute the totals of each category, once all of them are written
+    pub(super) fn record_outputs(
+        &mut self,
+        outputs: &Outputs,
+        proposal_bonus_output: Option<&Path>,
+        challenges: &Path,
+    ) -> Result<()> {
+        let voters = load_address_rewards(&outputs.voter_rewards_output)?;
+        self.totals.voters = CategoryTotal {
+            amount: voters.iter().map(|(_, amount)| amount).sum(),
+            recipients: voters
+                .iter()
+                .map(|(address, _)| address.to_bech32())
+                .collect::<BTreeSet<_>>()
+                .len(),
+        };
+        self.totals.veterans = advisors_total(&outputs.veterans_rewards_output)?;
+        self.totals.community_advisors = advisors_total(&outputs.ca_rewards_output)?;
+
+        let mut paths = vec![
+            outputs.voter_rewards_output.clone(),
+            outputs.veterans_rewards_output.clone(),
+            outputs.ca_rewards_output.clone(),
+        ];
+        if let Some(path) = &outputs.drep_rewards_output {
+            let dreps = load_address_rewards(path)?;
+            self.totals.dreps = Some(CategoryTotal {
+                amount: dreps.iter().map(|(_, amount)| amount).sum(),
+                recipients: dreps
+                    .iter()
+                    .map(|(address, _)| address.to_bech32())
+                    .collect::<BTreeSet<_>>()
+                    .len(),
+            });
+            paths.push(path.clone());
+        }
+        paths.extend(proposal_bonus_output.map(Path::to_path_buf));
+        paths.extend(outputs.dust_report_output.clone());
+
+        // proposer results are written in a file per challenge
+        let challenges: Vec<Challenge> = json_from_file(challenges)?;
+        let mut funded = BTreeSet::new();
+        for challenge in challenges {
+            let path = build_path_for_challenge(&outputs.proposer_rewards_output, &challenge.title);
+            if !path.exists() {
+                continue;
+            }
+            for result in load_data_from_csv::<ProposerResult, b','>(&path)? {
+                if matches!(result.status, FundedStatus::Funded) {
+                    self.totals.proposers.amount += u64::try_from(result.requested_dollars)?;
+                    funded.insert(result.proposal_id);
+                }
+            }
+            paths.push(path);
+        }
+        self.totals.proposers.recipients = funded.len();
+
+        self.outputs = paths
+            .iter()
+            .map(|path| FileHash::new(path))
+            .collect::<Result<_>>()?;
+        self.category_recipients = self.totals.voters.recipients
+            + self
+                .totals
+                .dreps
+                .as_ref()
+                .map_or(0, |dreps| dreps.recipients)
+            + self.totals.veterans.recipients
+            + self.totals.community_advisors.recipients
+            + self.totals.proposers.recipients;
+        Ok(())
+    }
+
+    pub(super) fn write(&self, path: &Path) -> Result<()> {
+        serde_json::to_writer_pretty(File::create(path)?, self)?;
+        Ok(())
+    }
+}
+
+fn advisors_total(path: &Path) -> Result<CategoryTotal> {
+    let rewards = load_data_from_csv::<AdvisorRewards, b','>(path)?;
+    Ok(CategoryTotal {
+        amount: rewards.iter().map(|row| row.rewards).sum(),
+        recipients: rewards
+            .iter()
+            .map(|row| &row.id)
+            .collect::<BTreeSet<_>>()
+            .len(),
+    })
+}
@@ -3,16 +3,34 @@ use std::{fs::File, path::Path};
 use catalyst_toolbox::{
     http::HttpClient,
     rewards::proposers::{OutputFormat, ProposerRewards},
+    rewards::rounding::DustReport,
 };
 use color_eyre::Result;
 use config::*;
+use manifest::Manifest;
+use serde::Serialize;
 use serde_json::from_reader;
+use snapshot_lib::reward_address::AddressEncoding;
 use tracing::info;
 
+use super::rounding::{write_dust_report, RoundingArgs};
+
 mod config;
+mod manifest;
+
+/// Dust reports of the rewards which are rounded to lovelace
+#[derive(Serialize)]
+struct DustReports {
+    voters: DustReport,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dreps: Option<DustReport>,
+    veterans: DustReport,
+}
 
 pub(super) fn full_rewards(path: &Path) -> Result<()> {
-    let config = from_reader(File::open(path)?)?;
+    let config = Config::from_value(&from_reader(File::open(path)?)?)?;
+    info!("hashing inputs");
+    let mut manifest = Manifest::new(path, &config.inputs, &config.params)?;
     let Config {
         inputs:
             Inputs {
@@ -28,36 +46,54 @@ pub(super) fn full_rewards(path: &Path) -> Result<()> {
                 proposals_path,
                 committee_keys,
                 excluded_proposals,
+                challenge_thresholds,
             },
-        outputs:
-            Outputs {
-                voter_rewards_output,
-                veterans_rewards_output,
-                ca_rewards_output,
-                proposer_rewards_output,
-            },
+        outputs,
         params:
             Params {
                 voter_params,
                 proposer_params,
                 ca_params,
                 vca_params,
+                drep_params,
+                rounding,
             },
     } = config;
+    let rounding = RoundingArgs::new(rounding, None);
 
     info!("calculating voter rewards");
-    super::voters::voter_rewards(
-        &voter_rewards_output,
+    let voters_dust = super::voters::voter_rewards(
+        &outputs.voter_rewards_output,
         &vote_count_path,
         &snapshot_path,
         voter_params.vote_threshold,
         voter_params.total_rewards,
+        AddressEncoding::Bech32,
+        &rounding,
     )?;
 
+    let mut dreps_dust = None;
+    if let (Some(drep_params), Some(drep_rewards_output)) =
+        (drep_params, &outputs.drep_rewards_output)
+    {
+        info!("calculating drep rewards");
+        dreps_dust = Some(super::dreps::drep_rewards(
+            drep_rewards_output,
+            &vote_count_path,
+            &snapshot_path,
+            drep_params.voting_group,
+            drep_params.top_dreps_to_reward,
+            drep_params.vote_threshold,
+            drep_params.total_rewards,
+            AddressEncoding::Bech32,
+            &rounding,
+        )?);
+    }
+
     info!("calculating vca rewards");
-    super::veterans::vca_rewards(
+    let veterans_dust = super::veterans::vca_rewards(
         reviews_csv,
-        veterans_rewards_output,
+        outputs.veterans_rewards_output.clone(),
         vca_params.rewards_agreement_rate_cutoffs,
         vca_params.rewards_agreement_rate_modifiers,
         vca_params.reputation_agreement_rate_cutoffs,
@@ -66,6 +102,7 @@ pub(super) fn full_rewards(path: &Path) -> Result<()> {
         vca_params.min_rankings,
         vca_params.max_rankings_reputation,
         vca_params.max_rankings_rewards,
+        &rounding,
     )?;
 
     info!("calculating ca rewards");
@@ -74,21 +111,22 @@ pub(super) fn full_rewards(path: &Path) -> Result<()> {
         approved_proposals_path,
         ca_params.fund_settings,
         ca_params.rewards_slots,
-        ca_rewards_output,
+        outputs.ca_rewards_output.clone(),
         ca_params.seed,
-        proposal_bonus_output,
+        proposal_bonus_output.clone(),
     )?;
 
     info!("calculating proposer rewards");
     super::proposers::rewards(
         &ProposerRewards {
-            output: proposer_rewards_output,
+            output: outputs.proposer_rewards_output.clone(),
             block0: block_file,
             total_stake_threshold: proposer_params.stake_threshold,
             approval_threshold: proposer_params.approval_threshold,
+            challenge_thresholds,
             proposals: Some(proposals_path),
             active_voteplans: Some(active_voteplans),
-            challenges: Some(challenges),
+            challenges: Some(challenges.clone()),
             committee_keys: Some(committee_keys),
             excluded_proposals,
             output_format: OutputFormat::Csv,
@@ -97,6 +135,25 @@ pub(super) fn full_rewards(path: &Path) -> Result<()> {
         &PanickingHttpClient,
     )?;
 
+    if let Some(path) = &outputs.dust_report_output {
+        info!("writing dust report");
+        write_dust_report(
+            path,
+            &DustReports {
+                voters: voters_dust,
+                dreps: dreps_dust,
+                veterans: veterans_dust,
+            },
+        )?;
+    }
+
+    info!("writing manifest");
+    manifest.record_outputs(&outputs, proposal_bonus_output.as_deref(), &challenges)?;
+    let manifest_output = outputs
+        .manifest_output
+        .unwrap_or_else(|| path.with_file_name("manifest.json"));
+    manifest.write(&manifest_output)?;
+
     Ok(())
 }
 
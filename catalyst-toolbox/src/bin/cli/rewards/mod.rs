@@ -1,7 +1,10 @@
 mod community_advisors;
 mod dreps;
 mod full;
+mod outputs;
+mod payout;
 mod proposers;
+mod rounding;
 mod veterans;
 mod voters;
 
@@ -39,6 +42,9 @@ pub enum Rewards {
 
     /// Calculate rewards for propsers
     Proposers(proposers_lib::ProposerRewards),
+
+    /// Merge rewards per address and split them into batches of payments
+    Payout(payout::Payout),
 }
 
 impl Rewards {
@@ -49,6 +55,7 @@ impl Rewards {
             Rewards::Veterans(cmd) => cmd.exec(),
             Rewards::Dreps(cmd) => cmd.exec(),
             Rewards::Full { path } => full::full_rewards(&path),
+            Rewards::Payout(cmd) => cmd.exec(),
             Rewards::Proposers(proposers) => {
                 proposers::rewards(&proposers, &default_http_client(None))
             }
@@ -0,0 +1,43 @@
+//! Rows of the csv files written by the rewards commands, as read back by `rewards payout`
+//! and `rewards full`.
+use catalyst_toolbox::rewards::proposers::FundedStatus;
+use color_eyre::eyre::WrapErr;
+use color_eyre::Report;
+use serde::Deserialize;
+use snapshot_lib::reward_address::RewardAddress;
+
+use std::path::Path;
+
+/// Row of the outputs of `rewards community-advisors` and `rewards veterans`
+#[derive(Deserialize)]
+pub(super) struct AdvisorRewards {
+    pub(super) id: String,
+    pub(super) rewards: u64,
+}
+
+/// Row of the csv output of `rewards proposers`
+#[derive(Deserialize)]
+pub(super) struct ProposerResult {
+    pub(super) proposal_id: String,
+    pub(super) requested_dollars: i64,
+    pub(super) status: FundedStatus,
+}
+
+pub(super) fn parse_address(address: &str) -> Result<RewardAddress, Report> {
+    address
+        .parse()
+        .wrap_err_with(|| format!("invalid reward address {}", address))
+}
+
+/// Read a csv file of addresses and amounts in lovelace, with a header row, such as the
+/// outputs of `rewards voters` and `rewards dreps`
+pub(super) fn load_address_rewards(path: &Path) -> Result<Vec<(RewardAddress, u64)>, Report> {
+    let mut reader = csv::Reader::from_path(path)?;
+    reader
+        .deserialize::<(String, u64)>()
+        .map(|row| {
+            let (address, amount) = row?;
+            Ok((parse_address(&address)?, amount))
+        })
+        .collect()
+}
@@ -0,0 +1,187 @@
+use catalyst_toolbox::rewards::payout::{apply_min_utxo, batch_payments, merge_payments, Payments};
+use catalyst_toolbox::rewards::proposers::FundedStatus;
+use catalyst_toolbox::utils::csv::load_data_from_csv;
+use color_eyre::eyre::eyre;
+use color_eyre::Report;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::Deserialize;
+use structopt::StructOpt;
+
+use super::outputs::{load_address_rewards, parse_address, AdvisorRewards, ProposerResult};
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+/// Merge the outputs of the rewards commands into batches of payments
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct Payout {
+    /// Voter rewards, as written by `rewards voters`
+    #[structopt(long)]
+    voters: Vec<PathBuf>,
+
+    /// Drep rewards, as written by `rewards dreps` or `rewards full`
+    #[structopt(long)]
+    dreps: Vec<PathBuf>,
+
+    /// Community advisor rewards, as written by `rewards community-advisors`
+    #[structopt(long)]
+    community_advisors: Vec<PathBuf>,
+
+    /// Veteran community advisor rewards, as written by `rewards veterans`
+    #[structopt(long)]
+    veterans: Vec<PathBuf>,
+
+    /// Csv file with `id` and `address` columns, mapping community advisors and veterans
+    /// to their reward address. Required when paying advisors.
+    #[structopt(long)]
+    advisor_addresses: Option<PathBuf>,
+
+    /// Proposer results for a challenge, as written by `rewards proposers` in csv format.
+    /// Funded proposals are paid their requested funds.
+    #[structopt(long)]
+    proposers: Vec<PathBuf>,
+
+    /// Csv file with `proposal_id` and `address` columns, mapping proposals to the reward
+    /// address of their proposer. Required when paying proposers.
+    #[structopt(long)]
+    proposer_addresses: Option<PathBuf>,
+
+    /// Lovelace paid for each unit of funds requested by proposers, e.g. the lovelace per USD
+    /// for funds requested in USD. Required when paying proposers.
+    #[structopt(long)]
+    proposer_rate: Option<Decimal>,
+
+    /// Minimum amount (in lovelace) of an output. Smaller amounts are not paid and are
+    /// carried forward to the ledger instead.
+    #[structopt(long)]
+    min_utxo: u64,
+
+    /// Ledger of amounts carried forward by a previous payout, which are added to this one
+    #[structopt(long)]
+    previous_ledger: Option<PathBuf>,
+
+    /// Where to write the ledger of amounts carried forward to the next payout
+    #[structopt(long)]
+    ledger: PathBuf,
+
+    /// Maximum number of outputs in each batch
+    #[structopt(long)]
+    max_outputs: NonZeroUsize,
+
+    /// Where to write the json encoded list of batches
+    #[structopt(long)]
+    output: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct AdvisorAddress {
+    id: String,
+    address: String,
+}
+
+#[derive(Deserialize)]
+struct ProposerAddress {
+    proposal_id: String,
+    address: String,
+}
+
+impl Payout {
+    pub fn exec(self) -> Result<(), Report> {
+        let Self {
+            voters,
+            dreps,
+            community_advisors,
+            veterans,
+            advisor_addresses,
+            proposers,
+            proposer_addresses,
+            proposer_rate,
+            min_utxo,
+            previous_ledger,
+            ledger,
+            max_outputs,
+            output,
+        } = self;
+
+        let mut payments = Vec::new();
+        for path in voters.iter().chain(&dreps) {
+            payments.extend(load_address_rewards(path)?);
+        }
+
+        if !community_advisors.is_empty() || !veterans.is_empty() {
+            let addresses = advisor_addresses
+                .ok_or_else(|| eyre!("--advisor-addresses is required to pay advisors"))?;
+            let addresses = load_data_from_csv::<AdvisorAddress, b','>(&addresses)?
+                .into_iter()
+                .map(|row| Ok((row.id, parse_address(&row.address)?)))
+                .collect::<Result<HashMap<_, _>, Report>>()?;
+            for path in community_advisors.iter().chain(&veterans) {
+                for row in load_data_from_csv::<AdvisorRewards, b','>(path)? {
+                    let address = addresses
+                        .get(&row.id)
+                        .ok_or_else(|| eyre!("no reward address for advisor {}", row.id))?;
+                    payments.push((address.clone(), row.rewards));
+                }
+            }
+        }
+
+        if !proposers.is_empty() {
+            let proposer_rate = proposer_rate
+                .ok_or_else(|| eyre!("--proposer-rate is required to pay proposers"))?;
+            let addresses = proposer_addresses
+                .ok_or_else(|| eyre!("--proposer-addresses is required to pay proposers"))?;
+            let addresses = load_data_from_csv::<ProposerAddress, b','>(&addresses)?
+                .into_iter()
+                .map(|row| Ok((row.proposal_id, parse_address(&row.address)?)))
+                .collect::<Result<HashMap<_, _>, Report>>()?;
+            for path in &proposers {
+                for row in load_data_from_csv::<ProposerResult, b','>(path)? {
+                    if !matches!(row.status, FundedStatus::Funded) {
+                        continue;
+                    }
+                    let address = addresses.get(&row.proposal_id).ok_or_else(|| {
+                        eyre!("no reward address for proposal {}", row.proposal_id)
+                    })?;
+                    let amount = (Decimal::from(row.requested_dollars) * proposer_rate)
+                        .trunc()
+                        .to_u64()
+                        .ok_or_else(|| eyre!("invalid amount for proposal {}", row.proposal_id))?;
+                    payments.push((address.clone(), amount));
+                }
+            }
+        }
+
+        let carried = match previous_ledger {
+            Some(path) => read_ledger(&path)?,
+            None => Payments::new(),
+        };
+        let (payable, carried) = apply_min_utxo(merge_payments(payments)?, carried, min_utxo)?;
+
+        write_ledger(&ledger, &carried)?;
+        serde_json::to_writer_pretty(
+            File::create(&output)?,
+            &batch_payments(&payable, max_outputs),
+        )?;
+        Ok(())
+    }
+}
+
+/// The ledger is a json map from bech32 encoded reward address to amount
+fn read_ledger(path: &Path) -> Result<Payments, Report> {
+    serde_json::from_reader::<_, BTreeMap<String, u64>>(File::open(path)?)?
+        .into_iter()
+        .map(|(address, amount)| Ok((parse_address(&address)?, amount)))
+        .collect()
+}
+
+fn write_ledger(path: &Path, ledger: &Payments) -> Result<(), Report> {
+    let ledger = ledger
+        .iter()
+        .map(|(address, amount)| (address.to_bech32(), *amount))
+        .collect::<BTreeMap<_, _>>();
+    serde_json::to_writer_pretty(File::create(path)?, &ledger)?;
+    Ok(())
+}
@@ -1,4 +1,7 @@
-use std::{collections::HashSet, fs::File};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+};
 
 use catalyst_toolbox::{
     http::HttpClient,
@@ -20,6 +23,7 @@ pub fn rewards(
         committee_keys,
         total_stake_threshold,
         approval_threshold,
+        challenge_thresholds,
         output_format,
         vit_station_url,
     }: &ProposerRewards,
@@ -39,6 +43,10 @@ pub fn rewards(
         Some(path) => serde_json::from_reader(File::open(path)?)?,
         None => HashSet::new(),
     };
+    let challenge_thresholds = match challenge_thresholds {
+        Some(path) => serde_json::from_reader(File::open(path)?)?,
+        None => HashMap::new(),
+    };
     let committee_keys = match committee_keys {
         Some(path) => serde_json::from_reader(File::open(path)?)?,
         None => vec![],
@@ -53,6 +61,7 @@ pub fn rewards(
         committee_keys,
         total_stake_threshold: *total_stake_threshold,
         approval_threshold: *approval_threshold,
+        challenge_thresholds,
     })?;
 
     write_results(output, *output_format, results)?;
@@ -0,0 +1,48 @@
+use catalyst_toolbox::rewards::rounding::{round_rewards, DustReport, Rounding};
+use catalyst_toolbox::rewards::Rewards;
+use color_eyre::Report;
+use structopt::StructOpt;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Rounding of rewards to the lovelace amounts paid out
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct RoundingArgs {
+    /// How rewards are rounded to integer amounts, either `truncate` or `largest-remainder`.
+    /// With `largest-remainder` the amounts paid out add up exactly to the distributed rewards.
+    #[structopt(long, default_value = "truncate")]
+    rounding: Rounding,
+
+    /// Write a json report of the amounts lost or redistributed by rounding
+    #[structopt(long, parse(from_os_str))]
+    dust_report: Option<PathBuf>,
+}
+
+impl RoundingArgs {
+    pub fn new(rounding: Rounding, dust_report: Option<PathBuf>) -> Self {
+        Self {
+            rounding,
+            dust_report,
+        }
+    }
+
+    /// Round `rewards` and write the dust report, if requested. The report is also returned.
+    pub fn round<K: Ord + Clone>(
+        &self,
+        rewards: &BTreeMap<K, Rewards>,
+        budget: u64,
+    ) -> Result<(BTreeMap<K, u64>, DustReport), Report> {
+        let (rounded, report) = round_rewards(rewards, budget, self.rounding)?;
+        if let Some(path) = &self.dust_report {
+            write_dust_report(path, &report)?;
+        }
+        Ok((rounded, report))
+    }
+}
+
+pub fn write_dust_report<T: serde::Serialize>(path: &Path, report: &T) -> Result<(), Report> {
+    serde_json::to_writer_pretty(std::fs::File::create(path)?, report)?;
+    Ok(())
+}
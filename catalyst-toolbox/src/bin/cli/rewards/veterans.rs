@@ -1,10 +1,13 @@
+use super::rounding::RoundingArgs;
 use catalyst_toolbox::community_advisors::models::VeteranRankingRow;
+use catalyst_toolbox::rewards::rounding::DustReport;
 use catalyst_toolbox::rewards::veterans::{self, VcaRewards, VeteranAdvisorIncentive};
 use catalyst_toolbox::utils::csv;
 use color_eyre::eyre::{bail, eyre};
 use color_eyre::Report;
 use rust_decimal::{prelude::*, Decimal};
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
@@ -61,6 +64,9 @@ pub struct VeteransRewards {
     /// if the first cutoff is selected then the first modifier is used.
     #[structopt(long, required = true)]
     reputation_agreement_rate_modifiers: Vec<Decimal>,
+
+    #[structopt(flatten)]
+    rounding: RoundingArgs,
 }
 
 impl VeteransRewards {
@@ -76,6 +82,7 @@ impl VeteransRewards {
             rewards_agreement_rate_modifiers,
             reputation_agreement_rate_cutoffs,
             reputation_agreement_rate_modifiers,
+            rounding,
         } = self;
 
         vca_rewards(
@@ -89,7 +96,9 @@ impl VeteransRewards {
             min_rankings,
             max_rankings_reputation,
             max_rankings_rewards,
-        )
+            &rounding,
+        )?;
+        Ok(())
     }
 }
 
@@ -105,7 +114,8 @@ pub fn vca_rewards(
     min_rankings: usize,
     max_rankings_reputation: usize,
     max_rankings_rewards: usize,
-) -> Result<(), Report> {
+    rounding: &RoundingArgs,
+) -> Result<DustReport, Report> {
     let reviews: Vec<VeteranRankingRow> = csv::load_data_from_csv::<_, b','>(&reviews_csv)?;
 
     if rewards_agreement_rate_cutoffs.len() != rewards_agreement_rate_modifiers.len() {
@@ -151,12 +161,21 @@ pub fn vca_rewards(
             .collect(),
     );
 
-    csv::dump_data_to_csv(rewards_to_csv_data(results).iter(), &output).unwrap();
+    let budget = total_rewards
+        .trunc()
+        .to_u64()
+        .ok_or_else(|| eyre!("Rewards overflow"))?;
+    let (rows, dust) = rewards_to_csv_data(results, rounding, budget)?;
+    csv::dump_data_to_csv(rows.iter(), &output).unwrap();
 
-    Ok(())
+    Ok(dust)
 }
 
-fn rewards_to_csv_data(rewards: VcaRewards) -> Result<Vec<impl Serialize>, Report> {
+fn rewards_to_csv_data(
+    rewards: VcaRewards,
+    rounding: &RoundingArgs,
+    budget: u64,
+) -> Result<(Vec<impl Serialize>, DustReport), Report> {
     #[derive(Serialize)]
     struct Entry {
         id: String,
@@ -164,27 +183,26 @@ fn rewards_to_csv_data(rewards: VcaRewards) -> Result<Vec<impl Serialize>, Repor
         reputation: u64,
     }
 
-    rewards
+    let (rounded, dust) = rounding.round(
+        &rewards
+            .iter()
+            .map(|(id, incentive)| (id.clone(), incentive.rewards))
+            .collect::<BTreeMap<_, _>>(),
+        budget,
+    )?;
+
+    let rows = rewards
         .into_iter()
-        .map(
-            |(
-                id,
-                VeteranAdvisorIncentive {
-                    rewards,
-                    reputation,
-                },
-            )| {
-                Ok(Entry {
-                    id,
-                    rewards: rewards.to_u64().ok_or_else(|| eyre!("Rewards overflow"))?,
-                    reputation,
-                })
-            },
-        )
-        .collect()
+        .map(|(id, VeteranAdvisorIncentive { reputation, .. })| Entry {
+            rewards: rounded[&id],
+            id,
+            reputation,
+        })
+        .collect();
+    Ok((rows, dust))
 }
 
-fn is_descending(v: &Vec<Decimal>) -> bool {
+pub(super) fn is_descending(v: &Vec<Decimal>) -> bool {
     let mut clone = v.clone();
     clone.sort_by(|a, b| b.cmp(a));
 
@@ -1,3 +1,4 @@
+use catalyst_toolbox::rewards::rounding::DustReport;
 use catalyst_toolbox::rewards::voters::calc_voter_rewards;
 use catalyst_toolbox::rewards::{Rewards, Threshold, VoteCount};
 use catalyst_toolbox::utils::assert_are_close;
@@ -8,9 +9,11 @@ use jcli_lib::block::open_output;
 use jcli_lib::jcli_lib::block::Common;
 
 use snapshot_lib::registration::MainnetRewardAddress;
+use snapshot_lib::reward_address::{AddressEncoding, RewardAddress};
 use snapshot_lib::SnapshotInfo;
 use structopt::StructOpt;
 
+use super::rounding::RoundingArgs;
 use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
@@ -37,19 +40,45 @@ pub struct VotersRewards {
     /// Number of global votes required to be able to receive voter rewards
     #[structopt(long, default_value)]
     vote_threshold: u64,
+
+    /// Encoding of reward addresses in the output, either bech32 or hex
+    #[structopt(long, default_value = "bech32")]
+    address_encoding: AddressEncoding,
+
+    #[structopt(flatten)]
+    rounding: RoundingArgs,
+}
+
+/// Re-encode a reward address from a snapshot with the requested encoding. Addresses which
+/// are not valid reward addresses cannot be paid and are rejected.
+pub fn encode_address(address: &MainnetRewardAddress, encoding: AddressEncoding) -> Result<String> {
+    address
+        .parse::<RewardAddress>()
+        .map(|reward_address| reward_address.encode(encoding))
+        .map_err(|e| eyre!("invalid reward address {}: {}", address, e))
 }
 
 fn write_rewards_results(
     common: &Option<PathBuf>,
-    rewards: &BTreeMap<MainnetRewardAddress, Rewards>,
+    rewards: &BTreeMap<MainnetRewardAddress, u64>,
+    address_encoding: AddressEncoding,
 ) -> Result<(), Report> {
+    // check all addresses before writing anything
+    let records = rewards
+        .iter()
+        .map(|(address, rewards)| {
+            Ok([
+                encode_address(address, address_encoding)?,
+                rewards.to_string(),
+            ])
+        })
+        .collect::<Result<Vec<_>>>()?;
+
     let writer = open_output(common)?;
     let header = ["Address", "Reward for the voter (lovelace)"];
     let mut csv_writer = csv::Writer::from_writer(writer);
     csv_writer.write_record(&header)?;
-
-    for (address, rewards) in rewards.iter() {
-        let record = [address.to_string(), rewards.trunc().to_string()];
+    for record in records {
         csv_writer.write_record(&record)?;
     }
 
@@ -64,6 +93,8 @@ impl VotersRewards {
             snapshot_info_path,
             votes_count_path,
             vote_threshold,
+            address_encoding,
+            rounding,
         } = self;
 
         voter_rewards(
@@ -75,7 +106,10 @@ impl VotersRewards {
             &snapshot_info_path,
             vote_threshold,
             total_rewards,
-        )
+            address_encoding,
+            &rounding,
+        )?;
+        Ok(())
     }
 }
 
@@ -85,7 +119,9 @@ pub fn voter_rewards(
     snapshot_path: &Path,
     vote_threshold: u64,
     total_rewards: u64,
-) -> Result<()> {
+    address_encoding: AddressEncoding,
+    rounding: &RoundingArgs,
+) -> Result<DustReport> {
     let vote_count: VoteCount = serde_json::from_reader(jcli_lib::utils::io::open_file_read(
         &Some(votes_count_path),
     )?)?;
@@ -107,6 +143,7 @@ pub fn voter_rewards(
     let actual_rewards = results.values().sum::<Rewards>();
     assert_are_close(actual_rewards, Rewards::from(total_rewards));
 
-    write_rewards_results(&Some(output.to_path_buf()), &results)?;
-    Ok(())
+    let (results, dust) = rounding.round(&results, total_rewards)?;
+    write_rewards_results(&Some(output.to_path_buf()), &results, address_encoding)?;
+    Ok(dust)
 }
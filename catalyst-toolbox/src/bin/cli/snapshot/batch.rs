@@ -0,0 +1,294 @@
+use super::{write_report, ProcessingArgs, ReportFormat};
+use color_eyre::Report;
+use jormungandr_lib::interfaces::Value;
+use serde::Serialize;
+use snapshot_lib::{
+    cbor, registration::VotingPurpose, voting_group::VotingGroupAssigner, Fraction, RawSnapshot,
+    SnapshotInfo, SnapshotOptions, UncappedSnapshot, CATALYST_VOTING_PURPOSE_TAG,
+};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Build one snapshot for each combination of voting purpose, stake threshold and voting
+/// power cap, reading raw registrations only once, and compare the results
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct Batch {
+    /// Path to the file containing all CIP-15 compatible registrations in json or CBOR format.
+    #[structopt(short, long, parse(from_os_str))]
+    snapshot: PathBuf,
+
+    /// Voting purpose to build snapshots for. Can be repeated.
+    /// If empty, defaults to the Catalyst voting purpose (0)
+    #[structopt(long = "voting-purpose")]
+    voting_purposes: Vec<VotingPurpose>,
+
+    /// Registrations voting power threshold for eligibility. Can be repeated.
+    #[structopt(short, long = "min-stake-threshold", required = true)]
+    min_stake_thresholds: Vec<Value>,
+
+    /// Voting power cap for each account. Can be repeated.
+    #[structopt(short, long = "voting-power-cap", required = true)]
+    voting_power_caps: Vec<Fraction>,
+
+    #[structopt(flatten)]
+    args: ProcessingArgs,
+
+    /// Directory to write snapshots to, one file for each combination of parameters.
+    /// Created if it does not exist.
+    #[structopt(long, parse(from_os_str))]
+    output_dir: PathBuf,
+
+    /// Write snapshots in CBOR instead of json
+    #[structopt(long)]
+    cbor: bool,
+
+    /// Output file for the comparison table, stdout if not provided
+    #[structopt(long, parse(from_os_str))]
+    comparison: Option<PathBuf>,
+
+    /// Format of the comparison table, either csv or json
+    #[structopt(long, default_value = "csv")]
+    format: ReportFormat,
+}
+
+#[derive(Debug, Serialize)]
+struct Comparison {
+    voting_purpose: VotingPurpose,
+    min_stake_threshold: Value,
+    voting_power_cap: String,
+    /// Name of the snapshot file in the output directory, if the snapshot could be built
+    file: Option<String>,
+    voters: usize,
+    total_voting_power: Value,
+    /// Total voting power after transformations, before the voting power cap
+    pre_cap_voting_power: Value,
+    /// Number of keys whose voting power was reduced by the cap
+    capped_keys: usize,
+    error: Option<String>,
+}
+
+impl Batch {
+    pub fn exec(self) -> Result<(), Report> {
+        let raw_snapshot: RawSnapshot = super::read_json_or_cbor(&self.snapshot)?;
+        let assigner = self.args.assigner()?;
+        let voting_purposes = if self.voting_purposes.is_empty() {
+            vec![CATALYST_VOTING_PURPOSE_TAG]
+        } else {
+            self.voting_purposes.clone()
+        };
+
+        let results = build_matrix(
+            &raw_snapshot,
+            &voting_purposes,
+            &self.min_stake_thresholds,
+            &self.voting_power_caps,
+            &assigner,
+            |voting_purpose| self.args.options(voting_purpose),
+            if self.cbor { "cbor" } else { "json" },
+        );
+        fs::create_dir_all(&self.output_dir)?;
+        let mut comparison = Vec::with_capacity(results.len());
+        for (row, voters) in results {
+            if let (Some(file), Some(voters)) = (&row.file, voters) {
+                let content = if self.cbor {
+                    cbor::to_vec(&voters)?
+                } else {
+                    serde_json::to_vec_pretty(&voters)?
+                };
+                File::create(self.output_dir.join(file))?.write_all(&content)?;
+            }
+            comparison.push(row);
+        }
+
+        write_report(self.comparison, self.format, &comparison, &comparison)
+    }
+}
+
+/// Build a snapshot for each combination of voting purpose, stake threshold and voting power
+/// cap, returning its comparison row and its voters, if it could be built. Registrations are
+/// processed once for each voting purpose and stake threshold, and only the cap is applied
+/// for each voting power cap. Snapshot file names end with `extension`.
+fn build_matrix(
+    raw_snapshot: &RawSnapshot,
+    voting_purposes: &[VotingPurpose],
+    thresholds: &[Value],
+    caps: &[Fraction],
+    assigner: &impl VotingGroupAssigner,
+    options: impl Fn(VotingPurpose) -> SnapshotOptions,
+    extension: &str,
+) -> Vec<(Comparison, Option<Vec<SnapshotInfo>>)> {
+    let mut results = Vec::new();
+    for &voting_purpose in voting_purposes {
+        let options = options(voting_purpose);
+        for threshold in thresholds {
+            let uncapped =
+                UncappedSnapshot::from_raw_snapshot(raw_snapshot, *threshold, assigner, &options)
+                    .map(|(uncapped, _)| uncapped)
+                    .map_err(|e| e.to_string());
+            for cap in caps {
+                let mut row = Comparison {
+                    voting_purpose,
+                    min_stake_threshold: *threshold,
+                    voting_power_cap: cap.to_string(),
+                    file: None,
+                    voters: 0,
+                    total_voting_power: 0.into(),
+                    pre_cap_voting_power: 0.into(),
+                    capped_keys: 0,
+                    error: None,
+                };
+                // a cap may be impossible to satisfy, which should not prevent comparing
+                // the other combinations
+                let snapshot = match uncapped
+                    .as_ref()
+                    .map_err(Clone::clone)
+                    .and_then(|uncapped| uncapped.cap(*cap).map_err(|e| e.to_string()))
+                {
+                    Ok(snapshot) => snapshot,
+                    Err(e) => {
+                        row.error = Some(e);
+                        results.push((row, None));
+                        continue;
+                    }
+                };
+
+                let voters = snapshot.to_full_snapshot_info();
+                row.voters = voters.len();
+                row.total_voting_power = voters
+                    .iter()
+                    .map(|v| u64::from(v.hir.voting_power))
+                    .sum::<u64>()
+                    .into();
+                row.pre_cap_voting_power = voters
+                    .iter()
+                    .map(|v| u64::from(v.pre_cap_voting_power))
+                    .sum::<u64>()
+                    .into();
+                row.capped_keys = voters.iter().filter(|v| v.cap_reduction > 0.into()).count();
+                row.file = Some(format!(
+                    "voting_purpose_{}_threshold_{}_cap_{}.{}",
+                    voting_purpose,
+                    u64::from(*threshold),
+                    row.voting_power_cap.replace('/', "-"),
+                    extension
+                ));
+                results.push((row, Some(voters)));
+            }
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jormungandr_lib::crypto::account::Identifier;
+    use snapshot_lib::{
+        registration::{Delegations, VotingRegistration},
+        Snapshot,
+    };
+
+    #[test]
+    fn test_matrix_matches_individual_runs() {
+        let registration =
+            |i: u8, voting_power: u64, voting_purpose: VotingPurpose| VotingRegistration {
+                stake_public_key: hex::encode([i; 32]),
+                voting_power: voting_power.into(),
+                reward_address: String::new(),
+                delegations: Delegations::Legacy(
+                    Identifier::from_hex(&hex::encode([i; 32])).unwrap(),
+                ),
+                voting_purpose,
+                nonce: None,
+                slot: None,
+            };
+        let raw_snapshot: RawSnapshot = vec![
+            registration(0, 1000, 0),
+            registration(1, 300, 0),
+            registration(2, 100, 0),
+            registration(3, 500, 1),
+            registration(4, 500, 1),
+        ]
+        .into();
+        let assigner = |_vk: &Identifier| String::new();
+        let options = |voting_purpose| SnapshotOptions::for_purposes([voting_purpose]);
+        let thresholds = [0.into(), 200.into()];
+        // a quarter of the voting power cannot be guaranteed with less than four voters
+        let caps = [
+            Fraction::from(1u64),
+            Fraction::new(1u64, 2u64),
+            Fraction::new(1u64, 4u64),
+        ];
+
+        let results = build_matrix(
+            &raw_snapshot,
+            &[0, 1],
+            &thresholds,
+            &caps,
+            &assigner,
+            options,
+            "json",
+        );
+        assert_eq!(results.len(), 2 * thresholds.len() * caps.len());
+
+        let mut results = results.into_iter();
+        for voting_purpose in [0, 1] {
+            for threshold in thresholds {
+                for cap in caps {
+                    let (row, voters) = results.next().unwrap();
+                    assert_eq!(row.voting_purpose, voting_purpose);
+                    assert_eq!(row.min_stake_threshold, threshold);
+                    assert_eq!(row.voting_power_cap, cap.to_string());
+                    match Snapshot::from_raw_snapshot_with_options(
+                        raw_snapshot.clone(),
+                        threshold,
+                        cap,
+                        &assigner,
+                        &options(voting_purpose),
+                    ) {
+                        Ok((snapshot, _)) => {
+                            let expected = snapshot.to_full_snapshot_info();
+                            assert_eq!(row.error, None);
+                            assert!(row.file.unwrap().ends_with(".json"));
+                            assert_eq!(row.voters, expected.len());
+                            assert_eq!(
+                                u64::from(row.total_voting_power),
+                                expected
+                                    .iter()
+                                    .map(|v| u64::from(v.hir.voting_power))
+                                    .sum::<u64>()
+                            );
+                            assert_eq!(voters, Some(expected));
+                        }
+                        Err(e) => {
+                            assert_eq!(row.error, Some(e.to_string()));
+                            assert_eq!(row.file, None);
+                            assert_eq!(voters, None);
+                        }
+                    }
+                }
+            }
+        }
+
+        // only the impossible cap fails, without affecting the other combinations
+        let results = build_matrix(
+            &raw_snapshot,
+            &[0],
+            &[0.into()],
+            &caps,
+            &assigner,
+            options,
+            "cbor",
+        );
+        assert_eq!(
+            results
+                .iter()
+                .map(|(row, voters)| (row.error.is_some(), voters.is_some()))
+                .collect::<Vec<_>>(),
+            vec![(false, true), (false, true), (true, false)]
+        );
+    }
+}
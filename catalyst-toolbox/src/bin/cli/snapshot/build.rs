@@ -0,0 +1,292 @@
+use super::{ProcessingArgs, SnapshotArgs};
+use color_eyre::{eyre::bail, Report};
+use jcli_lib::utils::{output_file::OutputFile, output_format::OutputFormat};
+use jormungandr_lib::interfaces::Value;
+use serde::Serialize;
+use snapshot_lib::{
+    apportionment::Apportionment,
+    cbor,
+    registration::VotingPurpose,
+    stream::{ReportEntry, ReportSink, SnapshotBuilder},
+    ExcludedVoter, Fraction, RawSnapshot, Snapshot, SnapshotReport, CATALYST_VOTING_PURPOSE_TAG,
+};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use structopt::StructOpt;
+
+/// Process raw registrations into blockchain initials
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct Build {
+    /// Path to the file containing all CIP-15 compatible registrations in json or CBOR format.
+    #[structopt(short, long, parse(from_os_str))]
+    snapshot: PathBuf,
+
+    #[structopt(flatten)]
+    args: SnapshotArgs,
+
+    /// Voting purpose to build the snapshot for. Can be repeated to produce one
+    /// snapshot per purpose in a single run.
+    /// If empty, defaults to the Catalyst voting purpose (0)
+    #[structopt(long = "voting-purpose")]
+    voting_purposes: Vec<VotingPurpose>,
+
+    /// Directory to write snapshots to, one file named `voting_purpose_<purpose>` for each
+    /// requested voting purpose. Required when more than one voting purpose is requested.
+    #[structopt(long, parse(from_os_str))]
+    output_dir: Option<PathBuf>,
+
+    /// Write a json report of the registrations that were superseded by a later one,
+    /// submitted after the registration deadline, for the wrong network or with a malformed
+    /// reward address, and of the voting keys below the threshold of their voting group,
+    /// keyed by voting purpose.
+    /// With --stream, the report is written while registrations are processed, as json lines
+    /// with one registration or voting key each, tagged with its voting purpose and the reason
+    /// it is reported.
+    #[structopt(long, parse(from_os_str))]
+    report: Option<PathBuf>,
+
+    /// Process registrations while reading them, without loading the whole file in memory.
+    /// Snapshots for all voting purposes are built in a single pass. Only json input is supported.
+    #[structopt(long)]
+    stream: bool,
+
+    /// Number of threads processing registrations when --stream is used
+    #[structopt(long, requires = "stream")]
+    threads: Option<NonZeroUsize>,
+
+    /// Write snapshots in CBOR instead of the format given by --output-format
+    #[structopt(long)]
+    cbor: bool,
+
+    #[structopt(flatten)]
+    output: OutputFile,
+
+    #[structopt(flatten)]
+    output_format: OutputFormat,
+}
+
+/// Arguments of `snapshot` from before it had subcommands, processed as `snapshot build`
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct LegacyBuild {
+    /// Path to the file containing all CIP-15 compatible registrations in json format.
+    /// Deprecated, use `snapshot build` instead
+    #[structopt(short, long, parse(from_os_str))]
+    snapshot: Option<PathBuf>,
+
+    /// Registrations voting power threshold for eligibility
+    #[structopt(short, long)]
+    min_stake_threshold: Option<Value>,
+
+    /// Voter group to assign direct voters to.
+    /// If empty, defaults to "voter"
+    #[structopt(short, long)]
+    direct_voters_group: Option<String>,
+
+    /// Voter group to assign representatives to.
+    /// If empty, defaults to "rep"
+    #[structopt(long)]
+    representatives_group: Option<String>,
+
+    /// Voting power cap for each account
+    #[structopt(short, long)]
+    voting_power_cap: Option<Fraction>,
+
+    #[structopt(flatten)]
+    output: OutputFile,
+
+    #[structopt(flatten)]
+    output_format: OutputFormat,
+}
+
+impl LegacyBuild {
+    pub fn into_build(self) -> Result<Build, Report> {
+        let snapshot = match self.snapshot {
+            Some(snapshot) => snapshot,
+            None => bail!("no subcommand given, try `snapshot --help`"),
+        };
+        let (min_stake_threshold, voting_power_cap) =
+            match (self.min_stake_threshold, self.voting_power_cap) {
+                (Some(threshold), Some(cap)) => (threshold, cap),
+                _ => bail!("--min-stake-threshold and --voting-power-cap are required"),
+            };
+        Ok(Build {
+            snapshot,
+            args: SnapshotArgs {
+                min_stake_threshold,
+                voting_power_cap,
+                processing: ProcessingArgs {
+                    direct_voters_group: self.direct_voters_group,
+                    representatives_group: self.representatives_group,
+                    reps_db_api_url: None,
+                    reps_db_file: None,
+                    registration_deadline_slot: None,
+                    transforms: Vec::new(),
+                    group_caps: Vec::new(),
+                    group_thresholds: Vec::new(),
+                    network: None,
+                    voting_groups_config: None,
+                    apportionment: Apportionment::default(),
+                },
+            },
+            voting_purposes: Vec::new(),
+            output_dir: None,
+            report: None,
+            stream: false,
+            threads: None,
+            cbor: false,
+            output: self.output,
+            output_format: self.output_format,
+        })
+    }
+}
+
+impl Build {
+    pub fn exec(self) -> Result<(), Report> {
+        let voting_purposes = if self.voting_purposes.is_empty() {
+            vec![CATALYST_VOTING_PURPOSE_TAG]
+        } else {
+            self.voting_purposes.clone()
+        };
+        if voting_purposes.len() > 1 && self.output_dir.is_none() {
+            bail!("--output-dir is required when more than one voting purpose is requested");
+        }
+        let assigner = self.args.assigner()?;
+        let threshold = self.args.min_stake_threshold;
+        let options = voting_purposes
+            .iter()
+            .map(|purpose| self.args.options(*purpose))
+            .collect::<Vec<_>>();
+
+        let mut streamed_report = None;
+        let results = if self.stream {
+            let mut reader = BufReader::new(File::open(&self.snapshot)?);
+            if !cbor::is_json(reader.fill_buf()?) {
+                bail!("--stream only supports registrations in json format");
+            }
+            streamed_report = self
+                .report
+                .as_deref()
+                .map(StreamedReport::create)
+                .transpose()?;
+            let sink = streamed_report.as_ref().map(StreamedReport::sink);
+            let builders = match self.threads {
+                Some(threads) => SnapshotBuilder::many_from_reader_parallel(
+                    reader, threshold, options, sink, threads,
+                )?,
+                None => SnapshotBuilder::many_from_reader(reader, threshold, options, sink)?,
+            };
+            builders
+                .into_iter()
+                .map(|builder| builder.build(self.args.voting_power_cap, &assigner))
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            let raw_snapshot: RawSnapshot = super::read_json_or_cbor(&self.snapshot)?;
+            options
+                .iter()
+                .map(|options| {
+                    Snapshot::from_raw_snapshot_with_options(
+                        raw_snapshot.clone(),
+                        threshold,
+                        self.args.voting_power_cap,
+                        &assigner,
+                        options,
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let mut reports = BTreeMap::new();
+        for (purpose, (snapshot, report)) in voting_purposes.into_iter().zip(results) {
+            reports.insert(purpose, report);
+            let content = if self.cbor {
+                cbor::to_vec(&snapshot.to_full_snapshot_info())?
+            } else {
+                self.output_format
+                    .format_json(serde_json::to_value(snapshot.to_full_snapshot_info())?)?
+                    .into_bytes()
+            };
+
+            match &self.output_dir {
+                Some(dir) => {
+                    let path = dir.join(format!("voting_purpose_{}", purpose));
+                    File::create(path)?.write_all(&content)?;
+                }
+                None => self.output.open()?.write_all(&content)?,
+            }
+        }
+
+        match (streamed_report, &self.report) {
+            (Some(streamed_report), _) => streamed_report.finish(reports)?,
+            (None, Some(path)) => serde_json::to_writer_pretty(File::create(path)?, &reports)?,
+            (None, None) => {}
+        }
+        Ok(())
+    }
+}
+
+/// Report written as json lines while registrations are processed, since the registrations
+/// it lists are not kept in memory when streaming
+struct StreamedReport(Arc<Mutex<ReportLines>>);
+
+struct ReportLines {
+    writer: BufWriter<File>,
+    /// The first error, after which nothing is written
+    result: Result<(), serde_json::Error>,
+}
+
+#[derive(Serialize)]
+struct ExcludedVoterLine<'a> {
+    reason: &'static str,
+    voting_purpose: VotingPurpose,
+    #[serde(flatten)]
+    voter: &'a ExcludedVoter,
+}
+
+impl ReportLines {
+    fn write(&mut self, line: &impl Serialize) {
+        if self.result.is_ok() {
+            self.result = serde_json::to_writer(&mut self.writer, line)
+                .and_then(|_| self.writer.write_all(b"\n").map_err(serde_json::Error::io));
+        }
+    }
+}
+
+impl StreamedReport {
+    fn create(path: &Path) -> Result<Self, Report> {
+        Ok(Self(Arc::new(Mutex::new(ReportLines {
+            writer: BufWriter::new(File::create(path)?),
+            result: Ok(()),
+        }))))
+    }
+
+    fn sink(&self) -> ReportSink {
+        let lines = Arc::clone(&self.0);
+        Arc::new(Mutex::new(move |entry: ReportEntry| {
+            lines.lock().expect("lock poisoned").write(&entry)
+        }))
+    }
+
+    /// Add the voting keys below the threshold of their voting group, which are only known
+    /// once snapshots are built
+    fn finish(self, reports: BTreeMap<VotingPurpose, SnapshotReport>) -> Result<(), Report> {
+        let mut lines = self.0.lock().expect("lock poisoned");
+        for (voting_purpose, report) in &reports {
+            for voter in &report.below_group_threshold {
+                lines.write(&ExcludedVoterLine {
+                    reason: "below_group_threshold",
+                    voting_purpose: *voting_purpose,
+                    voter,
+                });
+            }
+        }
+        std::mem::replace(&mut lines.result, Ok(()))?;
+        lines.writer.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+use color_eyre::Report;
+use jcli_lib::utils::io::open_file_write;
+use serde::Serialize;
+use snapshot_lib::{
+    merkle::{Hash, MerkleTree},
+    SnapshotInfo,
+};
+use std::fs::{self, File};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Compute the Merkle root committing to all voters in a snapshot, and optionally
+/// the inclusion proof for each voting key
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct Commit {
+    /// Path to the json or CBOR encoded list of `SnapshotInfo`, as produced by `snapshot build`
+    #[structopt(short, long, parse(from_os_str))]
+    snapshot: PathBuf,
+
+    /// Directory to write inclusion proofs to, one file named `<voting group>/<voting key>.json`
+    /// for each voter, as the same voting key can be part of several voting groups
+    #[structopt(long, parse(from_os_str))]
+    proofs_dir: Option<PathBuf>,
+
+    /// Output file for the commitment, stdout if not provided
+    #[structopt(long, parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct Commitment {
+    root: Hash,
+    voters: usize,
+}
+
+impl Commit {
+    pub fn exec(self) -> Result<(), Report> {
+        let snapshot: Vec<SnapshotInfo> = super::read_json_or_cbor(&self.snapshot)?;
+        let tree = MerkleTree::new(snapshot.into_iter().map(|info| info.hir).collect());
+        let proofs = tree.all_proofs();
+
+        if let Some(dir) = &self.proofs_dir {
+            for proof in &proofs {
+                let group_dir = dir.join(&proof.voter.voting_group);
+                fs::create_dir_all(&group_dir)?;
+                let path = group_dir.join(format!("{}.json", proof.voter.voting_key.to_hex()));
+                serde_json::to_writer_pretty(File::create(path)?, proof)?;
+            }
+        }
+
+        let commitment = Commitment {
+            root: tree.root(),
+            voters: proofs.len(),
+        };
+        serde_json::to_writer_pretty(open_file_write(&self.output)?, &commitment)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use color_eyre::{eyre::bail, Report};
+use jcli_lib::utils::io::open_file_write;
+use serde::{de::DeserializeOwned, Serialize};
+use snapshot_lib::{cbor, RawSnapshot, SnapshotInfo, VoterHIR};
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// Convert raw registrations, snapshots or voters between json and CBOR.
+/// The format of the input is detected automatically.
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct Convert {
+    /// Path to the file to convert
+    #[structopt(short, long, parse(from_os_str))]
+    input: PathBuf,
+
+    /// Content of the input file, one of `raw` (registrations), `snapshot-info`
+    /// (as produced by `snapshot build`) or `voters` (a list of `VoterHIR`)
+    #[structopt(long)]
+    kind: Kind,
+
+    /// Format to convert to, either json or cbor
+    #[structopt(long)]
+    to: Format,
+
+    /// Output file, stdout if not provided
+    #[structopt(long, parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Raw,
+    SnapshotInfo,
+    Voters,
+}
+
+impl FromStr for Kind {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "raw" => Ok(Self::Raw),
+            "snapshot-info" => Ok(Self::SnapshotInfo),
+            "voters" => Ok(Self::Voters),
+            other => bail!("invalid kind: {other}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Json,
+    Cbor,
+}
+
+impl FromStr for Format {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "cbor" => Ok(Self::Cbor),
+            other => bail!("invalid format: {other}"),
+        }
+    }
+}
+
+impl Convert {
+    pub fn exec(self) -> Result<(), Report> {
+        match self.kind {
+            Kind::Raw => self.convert::<RawSnapshot>(),
+            Kind::SnapshotInfo => self.convert::<Vec<SnapshotInfo>>(),
+            Kind::Voters => self.convert::<Vec<VoterHIR>>(),
+        }
+    }
+
+    fn convert<T: Serialize + DeserializeOwned>(&self) -> Result<(), Report> {
+        let value: T = super::read_json_or_cbor(&self.input)?;
+        let writer = open_file_write(&self.output)?;
+        match self.to {
+            Format::Json => serde_json::to_writer_pretty(writer, &value)?,
+            Format::Cbor => cbor::to_writer(writer, &value)?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use super::{write_report, ReportFormat, SnapshotArgs};
+use color_eyre::Report;
+use jormungandr_lib::interfaces::Value;
+use serde::Serialize;
+use snapshot_lib::{
+    registration::VotingPurpose, voting_group::DEFAULT_REPRESENTATIVE_GROUP, KeyContribution,
+    RawSnapshot, Snapshot, VotingGroup,
+};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// List the stake keys delegating to each representative, with the weight and value
+/// of each delegation
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct Delegators {
+    /// Path to the file containing all CIP-15 compatible registrations in json or CBOR format
+    #[structopt(short, long, parse(from_os_str))]
+    snapshot: PathBuf,
+
+    #[structopt(flatten)]
+    args: SnapshotArgs,
+
+    /// Voting purpose to build the snapshot for
+    #[structopt(long, default_value = "0")]
+    voting_purpose: VotingPurpose,
+
+    /// Voting group of representatives.
+    /// If empty, defaults to --representatives-group or "rep"
+    #[structopt(long)]
+    voting_group: Option<VotingGroup>,
+
+    /// Output file, stdout if not provided
+    #[structopt(long, parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Output format either csv or json
+    #[structopt(long, default_value = "json")]
+    format: ReportFormat,
+}
+
+#[derive(Debug, Serialize)]
+struct RepDelegators {
+    voting_key: String,
+    voting_power: Value,
+    delegators: Vec<KeyContribution>,
+}
+
+#[derive(Debug, Serialize)]
+struct DelegatorRecord {
+    voting_key: String,
+    stake_public_key: String,
+    reward_address: String,
+    weight: Option<u64>,
+    value: u64,
+    effective_value: Option<u64>,
+}
+
+impl Delegators {
+    pub fn exec(self) -> Result<(), Report> {
+        let raw_snapshot: RawSnapshot = super::read_json_or_cbor(&self.snapshot)?;
+        let (snapshot, _) = Snapshot::from_raw_snapshot_with_options(
+            raw_snapshot,
+            self.args.min_stake_threshold,
+            self.args.voting_power_cap,
+            &self.args.assigner()?,
+            &self.args.options(self.voting_purpose),
+        )?;
+        let voting_group = self
+            .voting_group
+            .clone()
+            .or_else(|| self.args.processing.representatives_group.clone())
+            .unwrap_or_else(|| DEFAULT_REPRESENTATIVE_GROUP.to_string());
+
+        let reps = snapshot
+            .to_voter_hir()
+            .into_iter()
+            .filter(|hir| hir.voting_group == voting_group)
+            .map(|hir| RepDelegators {
+                voting_key: hir.voting_key.to_hex(),
+                voting_power: hir.voting_power,
+                delegators: snapshot.contributions_for_voting_key(&hir.voting_key),
+            })
+            .collect::<Vec<_>>();
+
+        write_report(self.output, self.format, &reps, &to_records(&reps))
+    }
+}
+
+fn to_records(reps: &[RepDelegators]) -> Vec<DelegatorRecord> {
+    reps.iter()
+        .flat_map(|rep| {
+            rep.delegators.iter().map(|c| DelegatorRecord {
+                voting_key: rep.voting_key.clone(),
+                stake_public_key: c.stake_public_key.clone(),
+                reward_address: c.reward_address.clone(),
+                weight: c.weight,
+                value: c.value,
+                effective_value: c.effective_value,
+            })
+        })
+        .collect()
+}
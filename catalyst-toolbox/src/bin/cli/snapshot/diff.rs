@@ -0,0 +1,122 @@
+use super::{write_report, ReportFormat, SnapshotArgs};
+use color_eyre::Report;
+use jormungandr_lib::interfaces::Value;
+use serde::Serialize;
+use snapshot_lib::{
+    diff::{diff_raw_snapshots, diff_snapshot_info, Change, ContributionDiff, SnapshotDiff},
+    registration::VotingPurpose,
+    RawSnapshot, SnapshotInfo,
+};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Compare two snapshots, reporting added and removed voting keys, voting power
+/// and voting group changes, and contribution changes per stake key
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct Diff {
+    /// Path to the older snapshot
+    #[structopt(long, parse(from_os_str))]
+    old: PathBuf,
+
+    /// Path to the newer snapshot
+    #[structopt(long, parse(from_os_str))]
+    new: PathBuf,
+
+    /// Output file, stdout if not provided
+    #[structopt(long, parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Output format either csv or json
+    #[structopt(long, default_value = "json")]
+    format: ReportFormat,
+
+    #[structopt(subcommand)]
+    input: DiffInput,
+}
+
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub enum DiffInput {
+    /// Compare two json or CBOR encoded lists of `SnapshotInfo`
+    SnapshotInfo,
+    /// Compare two raw registrations files, processed with the same parameters
+    Raw {
+        #[structopt(flatten)]
+        args: SnapshotArgs,
+
+        /// Voting purpose to build the snapshots for
+        #[structopt(long, default_value = "0")]
+        voting_purpose: VotingPurpose,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct DiffRecord {
+    voting_key: String,
+    change: Change,
+    old_voting_group: Option<String>,
+    new_voting_group: Option<String>,
+    old_voting_power: Option<Value>,
+    new_voting_power: Option<Value>,
+    voting_power_delta: i128,
+    stake_public_key: Option<String>,
+    reward_address: Option<String>,
+    old_value: Option<u64>,
+    new_value: Option<u64>,
+}
+
+impl Diff {
+    pub fn exec(self) -> Result<(), Report> {
+        let diff = match &self.input {
+            DiffInput::SnapshotInfo => {
+                let old: Vec<SnapshotInfo> = super::read_json_or_cbor(&self.old)?;
+                let new: Vec<SnapshotInfo> = super::read_json_or_cbor(&self.new)?;
+                diff_snapshot_info(&old, &new)
+            }
+            DiffInput::Raw {
+                args,
+                voting_purpose,
+            } => {
+                let old: RawSnapshot = super::read_json_or_cbor(&self.old)?;
+                let new: RawSnapshot = super::read_json_or_cbor(&self.new)?;
+                diff_raw_snapshots(
+                    old,
+                    new,
+                    args.min_stake_threshold,
+                    args.voting_power_cap,
+                    &args.assigner()?,
+                    &args.options(*voting_purpose),
+                )?
+            }
+        };
+
+        write_report(self.output, self.format, &diff, &to_records(&diff))
+    }
+}
+
+fn to_records(diff: &SnapshotDiff) -> Vec<DiffRecord> {
+    diff.voters
+        .iter()
+        .flat_map(|voter| {
+            let record = |contribution: Option<&ContributionDiff>| DiffRecord {
+                voting_key: voter.voting_key.to_hex(),
+                change: voter.change,
+                old_voting_group: voter.old_voting_group.clone(),
+                new_voting_group: voter.new_voting_group.clone(),
+                old_voting_power: voter.old_voting_power,
+                new_voting_power: voter.new_voting_power,
+                voting_power_delta: voter.voting_power_delta,
+                stake_public_key: contribution.map(|c| c.stake_public_key.clone()),
+                reward_address: contribution.map(|c| c.reward_address.clone()),
+                old_value: contribution.map(|c| c.old_value),
+                new_value: contribution.map(|c| c.new_value),
+            };
+            if voter.contributions.is_empty() {
+                vec![record(None)]
+            } else {
+                voter.contributions.iter().map(Some).map(record).collect()
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,52 @@
+use color_eyre::{eyre::eyre, Report};
+use jcli_lib::utils::io::open_file_write;
+use jormungandr_lib::crypto::account::Identifier;
+use snapshot_lib::{explain::Explanation, voting_group::parse_voting_key, SnapshotInfo};
+use std::io::Write;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Explain how the voting power of a voting key was derived, from the value contributed
+/// by each stake key to the reduction applied by the voting power cap
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct Explain {
+    /// Path to a json or CBOR encoded list of `SnapshotInfo`, as produced by `snapshot build`
+    #[structopt(short, long, parse(from_os_str))]
+    snapshot: PathBuf,
+
+    /// Voting key to explain, in hex format with or without a `0x` prefix
+    #[structopt(long, parse(try_from_str = parse_voting_key))]
+    voting_key: Identifier,
+
+    /// Print the explanation as json instead of text
+    #[structopt(long)]
+    json: bool,
+
+    /// Output file, stdout if not provided
+    #[structopt(long, parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+impl Explain {
+    pub fn exec(self) -> Result<(), Report> {
+        let snapshot: Vec<SnapshotInfo> = super::read_json_or_cbor(&self.snapshot)?;
+        let info = snapshot
+            .iter()
+            .find(|info| info.hir.voting_key == self.voting_key)
+            .ok_or_else(|| {
+                eyre!(
+                    "voting key {} is not in the snapshot, it may have been excluded by a threshold",
+                    self.voting_key.to_hex()
+                )
+            })?;
+        let explanation = Explanation::new(info);
+        let mut writer = open_file_write(&self.output)?;
+        if self.json {
+            serde_json::to_writer_pretty(writer, &explanation)?;
+        } else {
+            writeln!(writer, "{}", explanation)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use color_eyre::Report;
+use jcli_lib::utils::io::open_file_write;
+use snapshot_lib::{cbor, db_sync::import_registrations};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Build raw registrations from CSV exports of db-sync tables, computing the voting power
+/// of each registration from the exported UTxO stake at a given slot
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct ImportDbSync {
+    /// Export of the registration (61284) and signature (61285) metadata, with columns
+    /// `tx_id`, `key`, `bytes` and `slot_no`
+    #[structopt(long, parse(from_os_str))]
+    metadata: PathBuf,
+
+    /// Export of the stake addresses, with columns `id` and `hash_raw`
+    #[structopt(long, parse(from_os_str))]
+    stake_addresses: PathBuf,
+
+    /// Export of the outputs delegated to a stake address, with columns `stake_address_id`,
+    /// `value`, `slot_no` and `spent_slot_no`
+    #[structopt(long, parse(from_os_str))]
+    utxos: PathBuf,
+
+    /// Slot at which voting power is computed. Registrations included in a later slot are rejected.
+    #[structopt(long)]
+    slot: u64,
+
+    /// Write a json list of the registrations which failed verification or were included
+    /// after the requested slot, with the reason
+    #[structopt(long, parse(from_os_str))]
+    rejected: Option<PathBuf>,
+
+    /// Write registrations in CBOR instead of json
+    #[structopt(long)]
+    cbor: bool,
+
+    /// Output file, stdout if not provided
+    #[structopt(long, parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+impl ImportDbSync {
+    pub fn exec(self) -> Result<(), Report> {
+        let import = import_registrations(
+            BufReader::new(File::open(&self.metadata)?),
+            BufReader::new(File::open(&self.stake_addresses)?),
+            BufReader::new(File::open(&self.utxos)?),
+            self.slot,
+        )?;
+
+        let writer = open_file_write(&self.output)?;
+        if self.cbor {
+            cbor::to_writer(writer, &import.registrations)?;
+        } else {
+            serde_json::to_writer_pretty(writer, &import.registrations)?;
+        }
+        if self.rejected.is_some() {
+            serde_json::to_writer_pretty(open_file_write(&self.rejected)?, &import.rejected)?;
+        }
+        Ok(())
+    }
+}
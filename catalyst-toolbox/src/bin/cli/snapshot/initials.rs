@@ -0,0 +1,82 @@
+use chain_addr::Discrimination;
+use chain_impl_mockchain::tokens::identifier::TokenIdentifier;
+use color_eyre::{eyre::bail, Report};
+use jcli_lib::utils::{output_file::OutputFile, output_format::OutputFormat};
+use jormungandr_lib::interfaces::Block0Configuration;
+use snapshot_lib::{block0::voters_to_block0_initials, SnapshotInfo, VotingGroup};
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Produce the block0 initial token sections minting the voting token of each
+/// voting group to the voting keys in that group
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct Initials {
+    /// Path to the json or CBOR encoded list of `SnapshotInfo`, as produced by `snapshot build`
+    #[structopt(short, long, parse(from_os_str))]
+    snapshot: PathBuf,
+
+    /// Voting token for a voting group, in the form `<group>=<policy hash>.<token name>`.
+    /// Must be provided for each voting group in the snapshot.
+    #[structopt(long = "voting-token", parse(try_from_str = parse_voting_token))]
+    voting_tokens: Vec<(VotingGroup, TokenIdentifier)>,
+
+    /// Path to an existing block0 configuration in yaml format. If provided, the initials
+    /// are appended to it and the whole configuration is written to the output.
+    #[structopt(long, parse(from_os_str))]
+    block0: Option<PathBuf>,
+
+    /// Set the discrimination type to testing (default is production).
+    #[structopt(short, long)]
+    testing: bool,
+
+    #[structopt(flatten)]
+    output: OutputFile,
+
+    #[structopt(flatten)]
+    output_format: OutputFormat,
+}
+
+fn parse_voting_token(s: &str) -> Result<(VotingGroup, TokenIdentifier), Report> {
+    match s.split_once('=') {
+        Some((group, token)) => Ok((group.to_string(), token.parse()?)),
+        None => bail!("invalid voting token {s}, expected <group>=<token id>"),
+    }
+}
+
+impl Initials {
+    pub fn exec(self) -> Result<(), Report> {
+        let snapshot: Vec<SnapshotInfo> = super::read_json_or_cbor(&self.snapshot)?;
+        let voters = snapshot
+            .into_iter()
+            .map(|info| info.hir)
+            .collect::<Vec<_>>();
+        let discrimination = if self.testing {
+            Discrimination::Test
+        } else {
+            Discrimination::Production
+        };
+        let initials = voters_to_block0_initials(
+            &voters,
+            discrimination,
+            &self.voting_tokens.into_iter().collect(),
+        )?;
+
+        let content = match &self.block0 {
+            Some(path) => {
+                let mut block0: Block0Configuration = serde_yaml::from_reader(File::open(path)?)?;
+                if block0.blockchain_configuration.discrimination != discrimination {
+                    bail!("block0 discrimination does not match the requested one");
+                }
+                block0.initial.extend(initials);
+                serde_json::to_value(block0)?
+            }
+            None => serde_json::to_value(initials)?,
+        };
+        let content = self.output_format.format_json(content)?;
+        self.output.open()?.write_all(content.as_bytes())?;
+        Ok(())
+    }
+}
@@ -1,27 +1,126 @@
-use color_eyre::Report;
-use jcli_lib::utils::{output_file::OutputFile, output_format::OutputFormat};
+mod batch;
+mod build;
+mod commit;
+mod convert;
+mod delegators;
+mod diff;
+mod explain;
+mod import_db_sync;
+mod initials;
+mod verify_proof;
+
+use color_eyre::{eyre::bail, Report};
+use jcli_lib::utils::io::open_file_write;
 use jormungandr_lib::interfaces::Value;
-use snapshot_lib::Fraction;
+use reqwest::Url;
+use serde::{de::DeserializeOwned, Serialize};
 use snapshot_lib::{
-    voting_group::{RepsVotersAssigner, DEFAULT_DIRECT_VOTER_GROUP, DEFAULT_REPRESENTATIVE_GROUP},
-    RawSnapshot, Snapshot,
+    apportionment::Apportionment,
+    cbor,
+    registration::VotingPurpose,
+    reward_address::Network,
+    transform::Transform,
+    voting_group::{
+        get_all_reps, load_reps_from_file, RepsVotersAssigner, RuleBasedAssigner, VoterInfo,
+        VotingGroupAssigner, DEFAULT_DIRECT_VOTER_GROUP, DEFAULT_REPRESENTATIVE_GROUP,
+    },
+    Fraction, SnapshotOptions, VotingGroup,
 };
-use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use structopt::StructOpt;
 
-/// Process raw registrations into blockchain initials
+/// Process raw registrations and inspect snapshots.
+///
+/// For backward compatibility, `snapshot` without a subcommand accepts the arguments it had
+/// before subcommands were introduced, and is equivalent to `snapshot build`.
 #[derive(StructOpt)]
-#[structopt(rename_all = "kebab-case")]
+#[structopt(
+    rename_all = "kebab-case",
+    setting = structopt::clap::AppSettings::ArgsNegateSubcommands
+)]
 pub struct SnapshotCmd {
-    /// Path to the file containing all CIP-15 compatible registrations in json format.
-    #[structopt(short, long, parse(from_os_str))]
-    snapshot: PathBuf,
+    #[structopt(flatten)]
+    legacy: build::LegacyBuild,
+
+    #[structopt(subcommand)]
+    command: Option<SnapshotSubcommand>,
+}
+
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub enum SnapshotSubcommand {
+    /// Process raw registrations into blockchain initials
+    Build(build::Build),
+    /// Compare two snapshots
+    Diff(diff::Diff),
+    /// Compute the Merkle root and inclusion proofs of a snapshot
+    Commit(commit::Commit),
+    /// Verify the inclusion proof of a voter against a snapshot Merkle root
+    VerifyProof(verify_proof::VerifyProof),
+    /// Produce block0 voting token initials from a snapshot
+    Initials(initials::Initials),
+    /// Convert raw registrations, snapshots or voters between json and CBOR
+    Convert(convert::Convert),
+    /// Explain how the voting power of a voting key was derived
+    Explain(explain::Explain),
+    /// Build raw registrations from CSV exports of db-sync tables
+    ImportDbSync(import_db_sync::ImportDbSync),
+    /// List the stake keys delegating to each representative
+    Delegators(delegators::Delegators),
+    /// Build snapshots for a matrix of voting purposes, thresholds and caps and compare them
+    Batch(batch::Batch),
+}
+
+impl SnapshotCmd {
+    pub fn exec(self) -> Result<(), Report> {
+        match self.command {
+            Some(command) => command.exec(),
+            None => self.legacy.into_build()?.exec(),
+        }
+    }
+}
+
+impl SnapshotSubcommand {
+    pub fn exec(self) -> Result<(), Report> {
+        match self {
+            Self::Build(cmd) => cmd.exec(),
+            Self::Diff(cmd) => cmd.exec(),
+            Self::Commit(cmd) => cmd.exec(),
+            Self::VerifyProof(cmd) => cmd.exec(),
+            Self::Initials(cmd) => cmd.exec(),
+            Self::Convert(cmd) => cmd.exec(),
+            Self::Explain(cmd) => cmd.exec(),
+            Self::ImportDbSync(cmd) => cmd.exec(),
+            Self::Delegators(cmd) => cmd.exec(),
+            Self::Batch(cmd) => cmd.exec(),
+        }
+    }
+}
+
+/// Parameters used to process raw registrations into a snapshot
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct SnapshotArgs {
     /// Registrations voting power threshold for eligibility
     #[structopt(short, long)]
     min_stake_threshold: Value,
 
+    /// Voting power cap for each account
+    #[structopt(short, long)]
+    voting_power_cap: Fraction,
+
+    #[structopt(flatten)]
+    processing: ProcessingArgs,
+}
+
+/// Parameters used to process raw registrations, other than the stake threshold
+/// and the voting power cap
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct ProcessingArgs {
     /// Voter group to assign direct voters to.
     /// If empty, defaults to "voter"
     #[structopt(short, long)]
@@ -32,39 +131,230 @@ pub struct SnapshotCmd {
     #[structopt(long)]
     representatives_group: Option<String>,
 
-    /// Voting power cap for each account
-    #[structopt(short, long)]
-    voting_power_cap: Fraction,
+    /// Url of the representatives db GraphQL api used to load representatives voting keys
+    #[structopt(long)]
+    reps_db_api_url: Option<Url>,
 
-    #[structopt(flatten)]
-    output: OutputFile,
+    /// Path to a json list or csv file (with a `voting_key` column) of representatives
+    /// voting keys. Can be used together with --reps-db-api-url.
+    #[structopt(long, parse(from_os_str))]
+    reps_db_file: Option<PathBuf>,
 
-    #[structopt(flatten)]
-    output_format: OutputFormat,
+    /// Discard registrations included in a block after this slot
+    #[structopt(long)]
+    registration_deadline_slot: Option<u64>,
+
+    /// Transformation applied to the voting power of each key before the voting power cap.
+    /// Can be repeated to chain multiple transformations, which are applied in order.
+    /// One of `quadratic`, `log[:<scale>]`, `ceiling:<voting power>` or `cap:<fraction>`
+    #[structopt(long = "transform")]
+    transforms: Vec<Transform>,
+
+    /// Voting power cap for a specific voting group, in the form `<group>=<fraction>`.
+    /// Groups without a specific cap use --voting-power-cap. Can be repeated.
+    #[structopt(long = "group-cap", parse(try_from_str = parse_group_param))]
+    group_caps: Vec<(VotingGroup, Fraction)>,
+
+    /// Minimum voting power of a voting key for a specific voting group, in the form
+    /// `<group>=<value>`. Applied to the total voting power of each key after voting
+    /// group assignment. Can be repeated.
+    #[structopt(long = "group-min-stake-threshold", parse(try_from_str = parse_group_param))]
+    group_thresholds: Vec<(VotingGroup, Value)>,
+
+    /// Discard registrations whose reward address is malformed or not for this network,
+    /// either mainnet or testnet
+    #[structopt(long)]
+    network: Option<Network>,
+
+    /// Path to a json or yaml file with the rules used to assign voting groups.
+    /// Cannot be used together with the representatives options.
+    #[structopt(long, parse(from_os_str))]
+    voting_groups_config: Option<PathBuf>,
+
+    /// How the voting power of CIP-36 registrations is split among multiple delegations,
+    /// either `last-key` (the last key receives the rounding remainder) or `largest-remainder`
+    #[structopt(long, default_value = "last-key")]
+    apportionment: Apportionment,
 }
 
-impl SnapshotCmd {
-    pub fn exec(self) -> Result<(), Report> {
-        let raw_snapshot: RawSnapshot = serde_json::from_reader(File::open(&self.snapshot)?)?;
+/// Voting group assigner selected from the command line
+pub enum Assigner {
+    Reps(RepsVotersAssigner),
+    Rules(RuleBasedAssigner),
+}
+
+impl VotingGroupAssigner for Assigner {
+    fn assign_voter(&self, voter: &VoterInfo) -> VotingGroup {
+        match self {
+            Self::Reps(assigner) => assigner.assign_voter(voter),
+            Self::Rules(assigner) => assigner.assign_voter(voter),
+        }
+    }
+}
+
+/// Parse a `<group>=<value>` pair
+fn parse_group_param<T>(s: &str) -> Result<(VotingGroup, T), Report>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match s.split_once('=') {
+        Some((group, value)) => Ok((group.to_string(), value.parse()?)),
+        None => bail!("invalid parameter {s}, expected <group>=<value>"),
+    }
+}
+
+impl SnapshotArgs {
+    fn options(&self, voting_purpose: VotingPurpose) -> SnapshotOptions {
+        self.processing.options(voting_purpose)
+    }
+
+    fn assigner(&self) -> Result<Assigner, Report> {
+        self.processing.assigner()
+    }
+}
+
+impl ProcessingArgs {
+    fn options(&self, voting_purpose: VotingPurpose) -> SnapshotOptions {
+        SnapshotOptions {
+            registration_deadline_slot: self.registration_deadline_slot,
+            transforms: self.transforms.clone(),
+            group_caps: self.group_caps.iter().cloned().collect(),
+            group_thresholds: self.group_thresholds.iter().cloned().collect(),
+            network: self.network,
+            apportionment: self.apportionment,
+            ..SnapshotOptions::for_purposes([voting_purpose])
+        }
+    }
+
+    fn assigner(&self) -> Result<Assigner, Report> {
+        if let Some(config) = &self.voting_groups_config {
+            if self.direct_voters_group.is_some()
+                || self.representatives_group.is_some()
+                || self.reps_db_api_url.is_some()
+                || self.reps_db_file.is_some()
+            {
+                bail!(
+                    "--voting-groups-config cannot be used together with representatives options"
+                );
+            }
+            return Ok(Assigner::Rules(RuleBasedAssigner::from_file(config)?));
+        }
         let direct_voter = self
             .direct_voters_group
+            .clone()
             .unwrap_or_else(|| DEFAULT_DIRECT_VOTER_GROUP.into());
         let representative = self
             .representatives_group
+            .clone()
             .unwrap_or_else(|| DEFAULT_REPRESENTATIVE_GROUP.into());
-        let assigner = RepsVotersAssigner::new(direct_voter, representative);
-        let initials = Snapshot::from_raw_snapshot(
-            raw_snapshot,
-            self.min_stake_threshold,
-            self.voting_power_cap,
-            &assigner,
-        )?
-        .to_full_snapshot_info();
-        let mut out_writer = self.output.open()?;
-        let content = self
-            .output_format
-            .format_json(serde_json::to_value(initials)?)?;
-        out_writer.write_all(content.as_bytes())?;
-        Ok(())
+        let mut repsdb = HashSet::new();
+        if let Some(url) = &self.reps_db_api_url {
+            repsdb.extend(get_all_reps(url.clone())?);
+        }
+        if let Some(file) = &self.reps_db_file {
+            repsdb.extend(load_reps_from_file(file)?);
+        }
+        Ok(Assigner::Reps(RepsVotersAssigner::new_from_repsdb(
+            direct_voter,
+            representative,
+            repsdb,
+        )))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ReportFormat {
+    Csv,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            other => bail!("invalid format: {other}"),
+        }
+    }
+}
+
+/// Read a json or CBOR encoded file. Anything that doesn't start with a json array or
+/// object is decoded as CBOR, with or without the self-describe tag.
+pub(crate) fn read_json_or_cbor<T: DeserializeOwned>(path: &Path) -> Result<T, Report> {
+    let bytes = std::fs::read(path)?;
+    if cbor::is_json(&bytes) {
+        Ok(serde_json::from_slice(&bytes)?)
+    } else {
+        Ok(cbor::from_slice(&bytes)?)
+    }
+}
+
+/// Write `report` as json, or `records` as csv, to `output` (stdout if not provided)
+fn write_report<R: Serialize, T: Serialize + Debug>(
+    output: Option<PathBuf>,
+    format: ReportFormat,
+    report: &R,
+    records: &[T],
+) -> Result<(), Report> {
+    match format {
+        ReportFormat::Json => serde_json::to_writer_pretty(open_file_write(&output)?, report)?,
+        ReportFormat::Csv => {
+            catalyst_toolbox::utils::csv::dump_to_csv_or_print(output, records.iter())?
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{CatalystCommand, Cli};
+
+    const LEGACY: [&str; 6] = ["-s", "raw.json", "-m", "100", "-v", "1/10"];
+
+    fn snapshot_cmd(args: &[&str]) -> Result<SnapshotCmd, structopt::clap::Error> {
+        Cli::from_iter_safe(args).map(|cli| match cli.command {
+            Some(CatalystCommand::Snapshot(cmd)) => cmd,
+            _ => panic!("expected the snapshot command"),
+        })
+    }
+
+    #[test]
+    fn test_legacy_invocation_is_processed_as_build() {
+        let mut args = vec!["catalyst-toolbox", "snapshot"];
+        args.extend(LEGACY);
+        let cmd = snapshot_cmd(&args).unwrap();
+        assert!(cmd.command.is_none());
+        assert!(cmd.legacy.into_build().is_ok());
+
+        // global flags before the command do not prevent recognizing it
+        let mut args = vec!["catalyst-toolbox", "--full-version", "snapshot"];
+        args.extend(LEGACY);
+        assert!(snapshot_cmd(&args).unwrap().command.is_none());
+    }
+
+    #[test]
+    fn test_subcommands_are_not_affected_by_legacy_args() {
+        let mut args = vec!["catalyst-toolbox", "snapshot", "build"];
+        args.extend(LEGACY);
+        let cmd = snapshot_cmd(&args).unwrap();
+        assert!(matches!(cmd.command, Some(SnapshotSubcommand::Build(_))));
+
+        // legacy arguments cannot be mixed with subcommands
+        let mut args = vec!["catalyst-toolbox", "snapshot"];
+        args.extend(LEGACY);
+        args.push("build");
+        assert!(snapshot_cmd(&args).is_err());
+    }
+
+    #[test]
+    fn test_incomplete_legacy_invocation_is_rejected() {
+        let cmd = snapshot_cmd(&["catalyst-toolbox", "snapshot", "-s", "raw.json"]).unwrap();
+        assert!(cmd.legacy.into_build().is_err());
+        let cmd = snapshot_cmd(&["catalyst-toolbox", "snapshot"]).unwrap();
+        assert!(cmd.legacy.into_build().is_err());
     }
 }
@@ -0,0 +1,39 @@
+use color_eyre::{eyre::bail, Report};
+use snapshot_lib::merkle::{Hash, InclusionProof};
+use std::fs::File;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Verify that a voter is included in a snapshot with the given Merkle root
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct VerifyProof {
+    /// Hex encoded Merkle root of the snapshot, as produced by `snapshot commit`
+    #[structopt(long)]
+    root: Hash,
+
+    /// Path to the json encoded inclusion proof
+    #[structopt(long, parse(from_os_str))]
+    proof: PathBuf,
+}
+
+impl VerifyProof {
+    pub fn exec(self) -> Result<(), Report> {
+        let proof: InclusionProof = serde_json::from_reader(File::open(&self.proof)?)?;
+        let root = proof.root();
+        if root != self.root {
+            bail!(
+                "invalid proof: expected root {}, computed root {}",
+                self.root,
+                root
+            );
+        }
+        println!(
+            "voting key {} is included in voting group {} with voting power {}",
+            proof.voter.voting_key.to_hex(),
+            proof.voter.voting_group,
+            proof.voter.voting_power
+        );
+        Ok(())
+    }
+}
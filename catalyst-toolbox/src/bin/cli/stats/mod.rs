@@ -1,12 +1,14 @@
 mod archive;
 mod live;
 mod snapshot;
+mod snapshot_info;
 mod voters;
 
 use archive::ArchiveCommand;
 use color_eyre::Report;
 use live::LiveStatsCommand;
 use snapshot::SnapshotCommand;
+use snapshot_info::SnapshotInfoCommand;
 use structopt::StructOpt;
 use voters::VotersCommand;
 
@@ -16,6 +18,7 @@ pub enum Stats {
     Live(LiveStatsCommand),
     Archive(ArchiveCommand),
     Snapshot(SnapshotCommand),
+    SnapshotInfo(SnapshotInfoCommand),
 }
 
 impl Stats {
@@ -25,6 +28,7 @@ impl Stats {
             Self::Live(live) => live.exec(),
             Self::Archive(archive) => archive.exec(),
             Self::Snapshot(snapshot) => snapshot.exec(),
+            Self::SnapshotInfo(snapshot_info) => snapshot_info.exec(),
         }
     }
 }
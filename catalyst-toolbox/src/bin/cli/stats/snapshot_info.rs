@@ -0,0 +1,44 @@
+use crate::cli::snapshot::read_json_or_cbor;
+use color_eyre::Report;
+use jcli_lib::utils::io::open_file_write;
+use snapshot_lib::{
+    metrics::{SnapshotMetrics, DEFAULT_TOP_N},
+    SnapshotInfo,
+};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Decentralization metrics of a snapshot (Gini coefficient, Nakamoto coefficient,
+/// top-N share and keys affected by the voting power cap), before and after the cap
+/// and for each voting group, in json format. Snapshots produced by versions of the toolbox
+/// which did not record the voting power before the cap are rejected.
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct SnapshotInfoCommand {
+    /// Path to the json or CBOR encoded list of `SnapshotInfo`, as produced by `snapshot build`
+    #[structopt(short, long, parse(from_os_str))]
+    snapshot: PathBuf,
+
+    /// Report the share of voting power held by the top N keys. Can be repeated.
+    /// If empty, defaults to 1, 10 and 100
+    #[structopt(long = "top")]
+    top_n: Vec<usize>,
+
+    /// Output file, stdout if not provided
+    #[structopt(long, parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+impl SnapshotInfoCommand {
+    pub fn exec(self) -> Result<(), Report> {
+        let snapshot: Vec<SnapshotInfo> = read_json_or_cbor(&self.snapshot)?;
+        let top_n = if self.top_n.is_empty() {
+            DEFAULT_TOP_N.to_vec()
+        } else {
+            self.top_n
+        };
+        let metrics = SnapshotMetrics::new(&snapshot, &top_n)?;
+        serde_json::to_writer_pretty(open_file_write(&self.output)?, &metrics)?;
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
 pub mod community_advisors;
 pub mod dreps;
+pub mod payout;
 pub mod proposers;
+pub mod rounding;
 pub mod veterans;
 pub mod voters;
 
@@ -19,6 +21,8 @@ use vit_servicing_station_lib::db::models::proposals::FullProposalInfo;
 pub enum Error {
     #[error("hash is not a valid blake2b256 hash")]
     InvalidHash(Vec<u8>),
+    #[error("reward amount overflow")]
+    Overflow,
 }
 
 pub struct Threshold {
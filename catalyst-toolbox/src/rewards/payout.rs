@@ -0,0 +1,113 @@
+//! Preparation of the payments of rewards: merging of the outputs of the different
+//! rewards calculations, carrying of amounts too small to be paid and batching.
+use super::Error;
+use serde::{Deserialize, Serialize};
+use snapshot_lib::reward_address::RewardAddress;
+use std::{collections::BTreeMap, num::NonZeroUsize};
+
+/// Amount in lovelace to be paid to each reward address
+pub type Payments = BTreeMap<RewardAddress, u64>;
+
+/// Add together the amounts paid to the same address
+pub fn merge_payments(
+    payments: impl IntoIterator<Item = (RewardAddress, u64)>,
+) -> Result<Payments, Error> {
+    let mut merged = Payments::new();
+    for (address, amount) in payments {
+        let entry = merged.entry(address).or_insert(0);
+        *entry = entry.checked_add(amount).ok_or(Error::Overflow)?;
+    }
+    Ok(merged)
+}
+
+/// Split `payments` into the ones which can be paid, and the ones below `min_utxo`.
+///
+/// Amounts carried over from previous payouts in `ledger` are added to the payments before
+/// comparing them to `min_utxo`. The returned ledger contains the amounts that should be
+/// carried forward to the next payout.
+pub fn apply_min_utxo(
+    payments: Payments,
+    ledger: Payments,
+    min_utxo: u64,
+) -> Result<(Payments, Payments), Error> {
+    let merged = merge_payments(payments.into_iter().chain(ledger))?;
+    Ok(merged
+        .into_iter()
+        .filter(|(_, amount)| *amount > 0)
+        .partition(|(_, amount)| *amount >= min_utxo))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentOutput {
+    /// Bech32 encoded reward address
+    pub address: String,
+    pub amount: u64,
+}
+
+/// A set of payments meant to be included in a single transaction
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Batch {
+    pub index: usize,
+    /// Sum of the amounts of all outputs
+    pub total: u64,
+    pub outputs: Vec<PaymentOutput>,
+}
+
+/// Split `payments` into batches of at most `max_outputs` outputs, in address order
+pub fn batch_payments(payments: &Payments, max_outputs: NonZeroUsize) -> Vec<Batch> {
+    let payments = payments.iter().collect::<Vec<_>>();
+    payments
+        .chunks(max_outputs.get())
+        .enumerate()
+        .map(|(index, chunk)| Batch {
+            index,
+            // the sum of all payments was checked for overflows when merging
+            total: chunk.iter().map(|(_, amount)| **amount).sum(),
+            outputs: chunk
+                .iter()
+                .map(|(address, amount)| PaymentOutput {
+                    address: address.to_bech32(),
+                    amount: **amount,
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(byte: u8) -> RewardAddress {
+        let mut bytes = vec![0xe1];
+        bytes.extend_from_slice(&[byte; 28]);
+        RewardAddress::from_bytes(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_small_amounts_are_carried() {
+        let payments =
+            merge_payments([(address(1), 600), (address(2), 500), (address(1), 600)]).unwrap();
+        let ledger = Payments::from([(address(2), 700), (address(3), 10)]);
+
+        let (payable, carried) = apply_min_utxo(payments, ledger, 1000).unwrap();
+        assert_eq!(
+            payable,
+            Payments::from([(address(1), 1200), (address(2), 1200)])
+        );
+        assert_eq!(carried, Payments::from([(address(3), 10)]));
+    }
+
+    #[test]
+    fn test_batches_do_not_exceed_max_outputs() {
+        let payments = (0..5).map(|i| (address(i), 100)).collect::<Payments>();
+        let batches = batch_payments(&payments, NonZeroUsize::new(2).unwrap());
+        assert_eq!(
+            batches.iter().map(|b| b.outputs.len()).collect::<Vec<_>>(),
+            vec![2, 2, 1]
+        );
+        assert_eq!(batches.iter().map(|b| b.total).sum::<u64>(), 500);
+        assert_eq!(batches[2].index, 2);
+        assert_eq!(batches[2].outputs[0].address, address(4).to_bech32());
+    }
+}
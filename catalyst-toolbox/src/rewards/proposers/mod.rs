@@ -12,6 +12,7 @@ use jormungandr_lib::{
         Address, Block0Configuration, Initial, Tally, VotePlanStatus, VoteProposalStatus,
     },
 };
+use rust_decimal::Decimal;
 use tracing::debug;
 use vit_servicing_station_lib::db::models::{challenges::Challenge, proposals::Proposal};
 
@@ -31,8 +32,10 @@ pub struct ProposerRewardsInputs {
     pub challenges: Vec<Challenge>,
     pub excluded_proposals: HashSet<String>,
     pub committee_keys: Vec<Address>,
-    pub total_stake_threshold: f64,
-    pub approval_threshold: f64,
+    pub total_stake_threshold: Decimal,
+    pub approval_threshold: Decimal,
+    /// Overrides of the thresholds for specific challenges, by challenge id
+    pub challenge_thresholds: HashMap<i32, ChallengeThresholds>,
 }
 
 pub fn proposer_rewards(
@@ -45,15 +48,17 @@ pub fn proposer_rewards(
         committee_keys,
         total_stake_threshold,
         approval_threshold,
+        challenge_thresholds,
     }: ProposerRewardsInputs,
 ) -> Result<Vec<(Challenge, Vec<Calculation>)>> {
     let (proposals, voteplans, challenges) = vecs_to_maps(proposals, voteplans, challenges)?;
     sanity_check_data(&proposals, &voteplans)?;
+    check_thresholds(total_stake_threshold, approval_threshold)?;
+    check_challenge_thresholds(&challenge_thresholds, &challenges)?;
 
     let proposals = filter_excluded_proposals(&proposals, &excluded_proposals);
 
     let Value(total_stake) = calculate_total_stake_from_block0(&block0_config, &committee_keys);
-    let total_stake_approval_threshold = total_stake_threshold * total_stake as f64;
 
     let mut result = Vec::with_capacity(challenges.len());
 
@@ -61,6 +66,15 @@ pub fn proposer_rewards(
         let (challenge_proposals, challenge_voteplan_proposals) =
             filter_data_by_challenge(id, &proposals, &voteplans);
 
+        let (total_stake_threshold, approval_threshold) = challenge_thresholds
+            .get(&id)
+            .copied()
+            .unwrap_or_default()
+            .resolve(total_stake_threshold, approval_threshold);
+        let total_stake_approval_threshold = total_stake_threshold
+            .checked_mul(Decimal::from(total_stake))
+            .ok_or_else(|| eyre!("total stake threshold overflow in challenge {id}"))?;
+
         let calculations = calculate_results(
             &challenge_proposals,
             &challenge_voteplan_proposals,
@@ -79,8 +93,8 @@ pub fn calculate_results(
     proposals: &HashMap<Hash, Proposal>,
     voteplans: &HashMap<Hash, VoteProposalStatus>,
     fund: i64,
-    threshold: f64,
-    total_stake_threshold: f64,
+    threshold: Decimal,
+    total_stake_threshold: Decimal,
 ) -> Result<Vec<Calculation>> {
     debug!("calculating. threshold: {threshold}, total_stake_threshold: {total_stake_threshold}");
     let success_results = calculate_vote_difference_and_threshold_success(
@@ -127,7 +141,7 @@ pub fn calculate_results(
             meets_approval_threshold: threshold_success.into(),
             requested_dollars: proposal.proposal_funds,
             status: funded.into(),
-            fund_depletion: depletion as f64,
+            fund_depletion: depletion,
             not_funded_reason,
             link_to_ideascale: proposal.proposal_url.clone(),
         });
@@ -139,8 +153,8 @@ pub fn calculate_results(
 fn calculate_vote_difference_and_threshold_success(
     proposals: &HashMap<Hash, Proposal>,
     voteplans: &HashMap<Hash, VoteProposalStatus>,
-    threshold: f64,
-    total_stake_threshold: f64,
+    threshold: Decimal,
+    total_stake_threshold: Decimal,
 ) -> Result<HashMap<Hash, (i64, bool)>> {
     let result = proposals
         .iter()
@@ -161,8 +175,8 @@ fn calculate_vote_difference_and_threshold_success(
 fn calculate_approval_threshold(
     proposal: &Proposal,
     voteplan: &VoteProposalStatus,
-    threshold: f64,
-    total_stake_threshold: f64,
+    threshold: Decimal,
+    total_stake_threshold: Decimal,
 ) -> Result<(i64, bool)> {
     debug!(
         "calculating approval threshold for proposal_id: {}",
@@ -172,18 +186,73 @@ fn calculate_approval_threshold(
     let (yes, no) = extract_yes_no_votes(proposal, voteplan)?;
     debug!("yes votes: {yes}, no votes: {no}");
 
-    let total = yes + no;
     let diff = yes as i64 - no as i64;
-    debug!("total: {total}, diff: {diff}");
+    debug!("diff: {diff}");
 
-    let pass_total_threshold = total as f64 >= total_stake_threshold;
-    let pass_relative_threshold = (yes as f64 / no as f64) >= threshold;
+    let success = passes_thresholds(yes, no, threshold, total_stake_threshold)?;
+    Ok((diff, success))
+}
+
+/// Whether `yes` and `no` votes pass both the approval threshold, the minimum ratio of yes
+/// to no votes, and the minimum total of votes. Proposals without no votes pass the approval
+/// threshold as long as they have some yes votes.
+fn passes_thresholds(
+    yes: u64,
+    no: u64,
+    threshold: Decimal,
+    total_stake_threshold: Decimal,
+) -> Result<bool> {
+    // the sum of two u64 always fits into a Decimal
+    let total = Decimal::from(yes) + Decimal::from(no);
+    debug!("total: {total}");
+
+    // compare exactly, without dividing: yes / no >= threshold <=> yes >= threshold * no
+    let pass_total_threshold = total >= total_stake_threshold;
+    let pass_relative_threshold = if no == 0 {
+        yes > 0
+    } else {
+        let required_yes = threshold
+            .checked_mul(Decimal::from(no))
+            .ok_or_else(|| eyre!("approval threshold overflow"))?;
+        Decimal::from(yes) >= required_yes
+    };
     let success = pass_total_threshold && pass_relative_threshold;
 
     debug!("total_stake_threshold: {total_stake_threshold}, threshold: {threshold}");
     debug!("success: {success}, total_threshold: {pass_total_threshold}, relative_threshold: {pass_relative_threshold}");
 
-    Ok((diff, success))
+    Ok(success)
+}
+
+/// Check that the total stake threshold is a fraction of the total stake, in [0, 1], and
+/// that the approval threshold is not negative
+fn check_thresholds(total_stake_threshold: Decimal, approval_threshold: Decimal) -> Result<()> {
+    if !(Decimal::ZERO..=Decimal::ONE).contains(&total_stake_threshold) {
+        bail!("total stake threshold {total_stake_threshold} is not between 0 and 1");
+    }
+    if approval_threshold.is_sign_negative() {
+        bail!("approval threshold {approval_threshold} is negative");
+    }
+    Ok(())
+}
+
+/// Check that all overridden thresholds are for known challenges and within the same ranges
+/// as the global thresholds
+fn check_challenge_thresholds<T>(
+    challenge_thresholds: &HashMap<i32, ChallengeThresholds>,
+    challenges: &HashMap<i32, T>,
+) -> Result<()> {
+    for (id, thresholds) in challenge_thresholds {
+        if !challenges.contains_key(id) {
+            bail!("thresholds overridden for unknown challenge {id}");
+        }
+        // only the overridden values are checked, the global ones are checked separately
+        let (total_stake_threshold, approval_threshold) =
+            thresholds.resolve(Decimal::ZERO, Decimal::ZERO);
+        check_thresholds(total_stake_threshold, approval_threshold)
+            .map_err(|e| eyre!("invalid thresholds for challenge {id}: {e}"))?;
+    }
+    Ok(())
 }
 
 /// returns (yes, no)
@@ -299,3 +368,94 @@ fn calculate_total_stake_from_block0(
         })
         .sum()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn approval() -> Decimal {
+        dec!(1.15)
+    }
+
+    #[test]
+    fn test_approval_threshold_is_exact() {
+        // 115 / 100 is exactly the threshold, which passes
+        assert!(passes_thresholds(115, 100, approval(), Decimal::ZERO).unwrap());
+        assert!(!passes_thresholds(114, 100, approval(), Decimal::ZERO).unwrap());
+        // 23 / 20 == 1.15, despite not being representable as a float
+        assert!(passes_thresholds(23, 20, approval(), Decimal::ZERO).unwrap());
+        assert!(passes_thresholds(
+            115_000_000_000_000,
+            100_000_000_000_000,
+            approval(),
+            Decimal::ZERO
+        )
+        .unwrap());
+        assert!(!passes_thresholds(
+            114_999_999_999_999,
+            100_000_000_000_000,
+            approval(),
+            Decimal::ZERO
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn test_no_votes_against() {
+        assert!(passes_thresholds(1, 0, approval(), Decimal::ZERO).unwrap());
+        assert!(!passes_thresholds(0, 0, approval(), Decimal::ZERO).unwrap());
+    }
+
+    #[test]
+    fn test_total_stake_threshold_is_inclusive() {
+        assert!(passes_thresholds(600, 400, Decimal::ONE, dec!(1000)).unwrap());
+        assert!(!passes_thresholds(600, 399, Decimal::ONE, dec!(1000)).unwrap());
+        // the total is not limited to u64
+        assert!(passes_thresholds(u64::MAX, u64::MAX, Decimal::ONE, dec!(1000)).unwrap());
+    }
+
+    #[test]
+    fn test_challenge_thresholds_override_global_ones() {
+        let overrides = ChallengeThresholds {
+            total_stake_threshold: None,
+            approval_threshold: Some(dec!(2)),
+        };
+        let (total_stake_threshold, approval_threshold) = overrides.resolve(dec!(0.01), approval());
+        assert_eq!(total_stake_threshold, dec!(0.01));
+        assert_eq!(approval_threshold, dec!(2));
+        // passes the global threshold, but not the overridden one
+        assert!(passes_thresholds(150, 100, approval(), Decimal::ZERO).unwrap());
+        assert!(!passes_thresholds(150, 100, approval_threshold, Decimal::ZERO).unwrap());
+
+        assert_eq!(
+            ChallengeThresholds::default().resolve(dec!(0.01), approval()),
+            (dec!(0.01), approval())
+        );
+    }
+
+    #[test]
+    fn test_challenge_thresholds_are_checked() {
+        let challenges = HashMap::from([(1, ())]);
+        let thresholds = |total_stake_threshold, approval_threshold| ChallengeThresholds {
+            total_stake_threshold,
+            approval_threshold,
+        };
+
+        let valid = HashMap::from([(1, thresholds(Some(Decimal::ONE), Some(dec!(1.5))))]);
+        assert!(check_challenge_thresholds(&valid, &challenges).is_ok());
+
+        let unknown = HashMap::from([(2, thresholds(None, None))]);
+        let error = check_challenge_thresholds(&unknown, &challenges).unwrap_err();
+        assert!(error.to_string().contains("unknown challenge 2"));
+
+        for invalid in [
+            thresholds(Some(dec!(1.01)), None),
+            thresholds(Some(dec!(-0.01)), None),
+            thresholds(None, Some(dec!(-1))),
+        ] {
+            let invalid = HashMap::from([(1, invalid)]);
+            assert!(check_challenge_thresholds(&invalid, &challenges).is_err());
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use jormungandr_lib::crypto::hash::Hash;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::{path::PathBuf, str::FromStr};
 use structopt::StructOpt;
@@ -47,11 +48,16 @@ pub struct ProposerRewards {
 
     #[structopt(default_value = "0.01")]
     #[structopt(long)]
-    pub total_stake_threshold: f64,
+    pub total_stake_threshold: Decimal,
 
     #[structopt(default_value = "1.15")]
     #[structopt(long)]
-    pub approval_threshold: f64,
+    pub approval_threshold: Decimal,
+
+    /// Path to a json-encoded map from challenge id to `ChallengeThresholds`,
+    /// overriding the thresholds above for some challenges
+    #[structopt(long = "challenge-thresholds-path")]
+    pub challenge_thresholds: Option<PathBuf>,
 
     #[structopt(default_value = "csv")]
     #[structopt(long)]
@@ -73,6 +79,30 @@ pub struct ProposerRewards {
     pub committee_keys: Option<PathBuf>,
 }
 
+/// Thresholds used for the proposals of a challenge instead of the global ones
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChallengeThresholds {
+    #[serde(default)]
+    pub total_stake_threshold: Option<Decimal>,
+    #[serde(default)]
+    pub approval_threshold: Option<Decimal>,
+}
+
+impl ChallengeThresholds {
+    /// The `(total_stake_threshold, approval_threshold)` to use for the challenge, taking the
+    /// global threshold for each value which is not overridden
+    pub fn resolve(
+        &self,
+        total_stake_threshold: Decimal,
+        approval_threshold: Decimal,
+    ) -> (Decimal, Decimal) {
+        (
+            self.total_stake_threshold.unwrap_or(total_stake_threshold),
+            self.approval_threshold.unwrap_or(approval_threshold),
+        )
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Calculation {
     pub internal_id: String,
@@ -85,7 +115,7 @@ pub struct Calculation {
     pub meets_approval_threshold: YesNo,
     pub requested_dollars: i64,
     pub status: FundedStatus,
-    pub fund_depletion: f64,
+    pub fund_depletion: i64,
     pub not_funded_reason: Option<NotFundedReason>,
     pub link_to_ideascale: String,
 }
@@ -0,0 +1,154 @@
+//! Rounding of fractional rewards to the integer amounts (e.g. lovelace) actually paid out.
+use super::{Error, Rewards};
+use color_eyre::{eyre::bail, Report};
+use rust_decimal::prelude::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rounding {
+    /// Each reward is truncated, the fractional parts are never paid out
+    Truncate,
+    /// Each reward is truncated, then the units left over are given one each to the
+    /// recipients with the largest fractional parts, so that the whole amount distributed
+    /// is paid out. Ties are broken by recipient order.
+    LargestRemainder,
+}
+
+impl Default for Rounding {
+    fn default() -> Self {
+        Self::Truncate
+    }
+}
+
+impl FromStr for Rounding {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "truncate" => Ok(Self::Truncate),
+            "largest-remainder" => Ok(Self::LargestRemainder),
+            other => bail!("invalid rounding mode: {other}"),
+        }
+    }
+}
+
+/// Amounts lost or redistributed when rounding rewards
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DustReport {
+    pub rounding: Rounding,
+    pub budget: u64,
+    /// Sum of the rounded rewards
+    pub paid: u64,
+    /// Part of the budget which is not paid out, either because of truncation or because
+    /// the fractional rewards do not add up to the whole budget (e.g. with no eligible recipients)
+    pub unpaid: u64,
+    /// Sum of the fractional parts of the rewards, which are lost when truncating
+    pub truncation_dust: Rewards,
+    pub recipients: usize,
+    /// Number of recipients receiving one unit more than their truncated reward
+    pub rounded_up: usize,
+}
+
+/// Round `rewards`, whose sum is expected not to exceed `budget`, to integer amounts.
+///
+/// With [`Rounding::LargestRemainder`] the rounded rewards add up to the sum of `rewards`
+/// rounded to the nearest integer, which is the whole budget when all of it is distributed.
+pub fn round_rewards<K: Ord + Clone>(
+    rewards: &BTreeMap<K, Rewards>,
+    budget: u64,
+    rounding: Rounding,
+) -> Result<(BTreeMap<K, u64>, DustReport), Error> {
+    let mut rounded = rewards
+        .iter()
+        .map(|(k, reward)| {
+            reward
+                .trunc()
+                .to_u64()
+                .map(|amount| (k.clone(), amount))
+                .ok_or(Error::Overflow)
+        })
+        .collect::<Result<BTreeMap<_, _>, _>>()?;
+    let truncated = rounded.values().try_fold(0u64, |acc, amount| {
+        acc.checked_add(*amount).ok_or(Error::Overflow)
+    })?;
+    let truncation_dust = rewards
+        .values()
+        .map(|reward| reward.fract())
+        .sum::<Rewards>();
+
+    let mut rounded_up = 0;
+    if rounding == Rounding::LargestRemainder {
+        let distributed = (Rewards::from(truncated) + truncation_dust)
+            .round()
+            .to_u64()
+            .ok_or(Error::Overflow)?
+            .min(budget);
+        let mut remainders = rewards.iter().collect::<Vec<_>>();
+        // stable sort, keys are already in order
+        remainders.sort_by(|(_, a), (_, b)| b.fract().cmp(&a.fract()));
+        for (k, _) in remainders
+            .into_iter()
+            .take(distributed.saturating_sub(truncated) as usize)
+        {
+            *rounded.get_mut(k).expect("all keys are present") += 1;
+            rounded_up += 1;
+        }
+    }
+
+    let paid = truncated + rounded_up as u64;
+    let report = DustReport {
+        rounding,
+        budget,
+        paid,
+        unpaid: budget.saturating_sub(paid),
+        truncation_dust,
+        recipients: rounded.len(),
+        rounded_up,
+    };
+    Ok((rounded, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_largest_remainder_pays_the_budget() {
+        let rewards = BTreeMap::from([
+            ("a", dec!(3.3333)),
+            ("b", dec!(3.3333)),
+            ("c", dec!(3.3334)),
+        ]);
+        let (rounded, report) = round_rewards(&rewards, 10, Rounding::LargestRemainder).unwrap();
+        assert_eq!(rounded, BTreeMap::from([("a", 3), ("b", 3), ("c", 4)]));
+        assert_eq!(report.paid, 10);
+        assert_eq!(report.unpaid, 0);
+        assert_eq!(report.rounded_up, 1);
+
+        let (rounded, report) = round_rewards(&rewards, 10, Rounding::Truncate).unwrap();
+        assert_eq!(rounded, BTreeMap::from([("a", 3), ("b", 3), ("c", 3)]));
+        assert_eq!(report.paid, 9);
+        assert_eq!(report.unpaid, 1);
+        assert_eq!(report.truncation_dust, dec!(1.0000));
+    }
+
+    #[test]
+    fn test_ties_are_broken_by_key() {
+        let rewards = BTreeMap::from([("b", dec!(0.5)), ("a", dec!(0.5))]);
+        let (rounded, _) = round_rewards(&rewards, 1, Rounding::LargestRemainder).unwrap();
+        assert_eq!(rounded, BTreeMap::from([("a", 1), ("b", 0)]));
+    }
+
+    #[test]
+    fn test_undistributed_budget_is_reported() {
+        let rewards = BTreeMap::<&str, Rewards>::new();
+        let (rounded, report) = round_rewards(&rewards, 100, Rounding::LargestRemainder).unwrap();
+        assert!(rounded.is_empty());
+        assert_eq!(report.paid, 0);
+        assert_eq!(report.unpaid, 100);
+    }
+}
@@ -309,6 +309,8 @@ mod tests {
                 reward_address,
                 delegations,
                 voting_purpose: 0,
+                nonce: None,
+                slot: None,
             });
             total_stake += i;
         }
@@ -356,6 +358,8 @@ mod tests {
                 reward_address,
                 delegations,
                 voting_purpose: 0,
+                nonce: None,
+                slot: None,
             });
         }
 
@@ -0,0 +1,223 @@
+//! Exact integer apportionment of an amount among weighted parties.
+use std::{collections::BTreeMap, fmt, str::FromStr};
+use thiserror::Error;
+
+/// Method used to split the voting power of a CIP-36 registration among its delegations
+///
+/// The textual representation is `last-key` or `largest-remainder` (also `hamilton`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Apportionment {
+    /// Each key receives the integer part of its share, while the last key in the
+    /// registration receives all the rounding remainder. Keys whose share rounds to 0,
+    /// except the last one, are dropped.
+    LastKey,
+    /// Largest remainder (Hamilton) method, see [`largest_remainder`].
+    /// Weights of repeated keys are added together and ties are broken by voting key,
+    /// so that the split does not depend on the order of delegations.
+    /// If all weights are 0, the voting power is split equally.
+    /// Keys whose share rounds to 0 are kept, with a value of 0.
+    LargestRemainder,
+}
+
+impl Default for Apportionment {
+    fn default() -> Self {
+        Self::LastKey
+    }
+}
+
+impl Apportionment {
+    /// Split `total` among `delegations` according to their weights. With
+    /// [`Apportionment::LastKey`], keys receiving no voting power are omitted, except for the
+    /// last key, while [`Apportionment::LargestRemainder`] returns every key exactly once.
+    ///
+    /// Panics if `delegations` is empty.
+    pub fn split<K: Ord + Clone>(&self, total: u64, delegations: &[(K, u32)]) -> Vec<(K, u64)> {
+        assert!(!delegations.is_empty(), "at least 1 delegation is required");
+        match self {
+            Self::LastKey => {
+                let (last, others) = delegations.split_last().expect("checked above");
+                let total_weights = delegations.iter().map(|(_, w)| *w as u128).sum::<u128>();
+                let mut res = Vec::with_capacity(delegations.len());
+                if total_weights > 0 {
+                    for (key, weight) in others {
+                        let value = (total as u128 * *weight as u128 / total_weights) as u64;
+                        if value > 0 {
+                            res.push((key.clone(), value));
+                        }
+                    }
+                }
+                let distributed = res.iter().map(|(_, value)| value).sum::<u64>();
+                res.push((last.0.clone(), total - distributed));
+                res
+            }
+            Self::LargestRemainder => {
+                let mut weights = BTreeMap::new();
+                for (key, weight) in delegations {
+                    *weights.entry(key.clone()).or_insert(0u64) += *weight as u64;
+                }
+                if weights.values().all(|w| *w == 0) {
+                    weights.values_mut().for_each(|w| *w = 1);
+                }
+                let values =
+                    largest_remainder(total, &weights.values().copied().collect::<Vec<_>>());
+                weights.into_keys().zip(values).collect()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Apportionment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LastKey => f.write_str("last-key"),
+            Self::LargestRemainder => f.write_str("largest-remainder"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("unknown apportionment method {0}, expected last-key or largest-remainder")]
+pub struct ParseApportionmentError(String);
+
+impl FromStr for Apportionment {
+    type Err = ParseApportionmentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "last-key" => Ok(Self::LastKey),
+            "largest-remainder" | "hamilton" => Ok(Self::LargestRemainder),
+            _ => Err(ParseApportionmentError(s.to_string())),
+        }
+    }
+}
+
+/// Split `total` proportionally to `weights` using the largest remainder (Hamilton) method.
+///
+/// Each party first receives the integer part of its exact quota `total * weight / sum(weights)`,
+/// then the units left over are given one each to the parties with the largest remainders.
+/// Ties are broken in favour of the party appearing first in `weights`, so that the result
+/// is deterministic. The returned amounts always sum to `total`, unless all weights are 0,
+/// in which case nothing is distributed.
+pub fn largest_remainder(total: u64, weights: &[u64]) -> Vec<u64> {
+    let weights_total = weights.iter().map(|w| *w as u128).sum::<u128>();
+    if weights_total == 0 {
+        return vec![0; weights.len()];
+    }
+
+    let mut shares = Vec::with_capacity(weights.len());
+    let mut remainders = Vec::with_capacity(weights.len());
+    for (i, weight) in weights.iter().enumerate() {
+        let quota = total as u128 * *weight as u128;
+        // quotas never exceed total, so they fit into an u64
+        shares.push((quota / weights_total) as u64);
+        remainders.push((quota % weights_total, i));
+    }
+
+    let distributed = shares.iter().map(|s| *s as u128).sum::<u128>();
+    // there are less left over units than parties, since each remainder is less than 1
+    let left_over = (total as u128 - distributed) as usize;
+    remainders.sort_unstable_by(|(a, i), (b, j)| b.cmp(a).then(i.cmp(j)));
+    for (_, i) in remainders.into_iter().take(left_over) {
+        shares[i] += 1;
+    }
+    shares
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::{collection::vec, prelude::*};
+    use test_strategy::proptest;
+
+    #[test]
+    fn test_remainders_are_distributed() {
+        assert_eq!(largest_remainder(10, &[1, 1, 1]), vec![4, 3, 3]);
+        assert_eq!(largest_remainder(10, &[1, 2, 2]), vec![2, 4, 4]);
+        assert_eq!(largest_remainder(7, &[30, 30, 40]), vec![2, 2, 3]);
+        assert_eq!(largest_remainder(5, &[0, 0]), vec![0, 0]);
+        assert_eq!(largest_remainder(0, &[1, 2]), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_last_key_receives_remainder() {
+        let delegations = [("a", 1), ("b", 1), ("c", 1)];
+        assert_eq!(
+            Apportionment::LastKey.split(10, &delegations),
+            vec![("a", 3), ("b", 3), ("c", 4)]
+        );
+        assert_eq!(
+            Apportionment::LastKey.split(1, &delegations),
+            vec![("c", 1)]
+        );
+        assert_eq!(
+            Apportionment::LargestRemainder.split(10, &delegations),
+            vec![("a", 4), ("b", 3), ("c", 3)]
+        );
+    }
+
+    #[test]
+    fn test_repeated_and_zero_weight_keys() {
+        assert_eq!(
+            Apportionment::LargestRemainder.split(10, &[("b", 1), ("a", 2), ("b", 1)]),
+            vec![("a", 5), ("b", 5)]
+        );
+        assert_eq!(
+            Apportionment::LargestRemainder.split(3, &[("b", 0), ("a", 0)]),
+            vec![("a", 2), ("b", 1)]
+        );
+        assert_eq!(
+            Apportionment::LargestRemainder.split(10, &[("b", 1), ("a", 0)]),
+            vec![("a", 0), ("b", 10)]
+        );
+    }
+
+    #[test]
+    fn test_keys_rounded_to_zero_are_kept() {
+        assert_eq!(
+            Apportionment::LargestRemainder.split(1, &[("a", 1), ("b", 1), ("c", 1)]),
+            vec![("a", 1), ("b", 0), ("c", 0)]
+        );
+        assert_eq!(
+            Apportionment::LargestRemainder.split(0, &[("b", 1), ("a", 1)]),
+            vec![("a", 0), ("b", 0)]
+        );
+    }
+
+    #[proptest]
+    fn test_split_does_not_depend_on_order(
+        total: u64,
+        #[strategy(vec((0..10u8, any::<u32>()), 1..10))] delegations: Vec<(u8, u32)>,
+        rotation: usize,
+    ) {
+        let mut shuffled = delegations.clone();
+        shuffled.reverse();
+        shuffled.rotate_left(rotation % delegations.len());
+        let split = Apportionment::LargestRemainder.split(total, &delegations);
+        assert_eq!(
+            split,
+            Apportionment::LargestRemainder.split(total, &shuffled)
+        );
+        let mut keys = delegations.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+        keys.sort_unstable();
+        keys.dedup();
+        assert_eq!(split.iter().map(|(key, _)| *key).collect::<Vec<_>>(), keys);
+        assert_eq!(
+            split.iter().map(|(_, v)| *v as u128).sum::<u128>(),
+            total as u128
+        );
+    }
+
+    #[proptest]
+    fn test_total_is_conserved(
+        total: u64,
+        #[strategy(vec(any::<u64>(), 1..20))] weights: Vec<u64>,
+    ) {
+        let shares = largest_remainder(total, &weights);
+        let distributed = shares.iter().map(|s| *s as u128).sum::<u128>();
+        if weights.iter().all(|w| *w == 0) {
+            assert_eq!(distributed, 0);
+        } else {
+            assert_eq!(distributed, total as u128);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{Snapshot, VoterHIR, VotingGroup};
+use chain_addr::{Discrimination, Kind};
+use chain_impl_mockchain::tokens::{identifier::TokenIdentifier, minting_policy::MintingPolicy};
+use jormungandr_lib::{
+    crypto::account::Identifier,
+    interfaces::{Address, Destination, Initial, InitialToken, InitialUTxO},
+};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+/// Maximum number of destinations in a single [`Initial::Token`] section. Initial tokens are
+/// distributed by transactions in block0, which encode the number of outputs as an u8.
+pub const MAX_TOKEN_DESTINATIONS: usize = 255;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("no voting token specified for voting group '{0}'")]
+    MissingToken(VotingGroup),
+}
+
+fn account_address(voting_key: &Identifier, discrimination: Discrimination) -> Address {
+    chain_addr::Address(discrimination, Kind::Account(voting_key.to_inner().into())).into()
+}
+
+/// Mint the voting token of each voting group to the voting keys in that group.
+///
+/// [`Initial::Token`] sections are produced for each voting group, in voting group order,
+/// ready to be appended to the initial sections of a `Block0Configuration`. Voting groups with
+/// more than [`MAX_TOKEN_DESTINATIONS`] voters are split into multiple sections for the same token.
+/// Each voting key receives an amount of tokens equal to its voting power.
+pub fn voters_to_block0_initials(
+    voters: &[VoterHIR],
+    discrimination: Discrimination,
+    voting_tokens: &HashMap<VotingGroup, TokenIdentifier>,
+) -> Result<Vec<Initial>, Error> {
+    let mut groups = BTreeMap::new();
+    for voter in voters {
+        groups
+            .entry(&voter.voting_group)
+            .or_insert_with(Vec::new)
+            .push(Destination {
+                address: account_address(&voter.voting_key, discrimination),
+                value: voter.voting_power,
+            });
+    }
+
+    let mut initials = Vec::new();
+    for (group, to) in groups {
+        let token_id = voting_tokens
+            .get(group)
+            .ok_or_else(|| Error::MissingToken(group.clone()))?;
+        initials.extend(to.chunks(MAX_TOKEN_DESTINATIONS).map(|to| {
+            Initial::Token(InitialToken {
+                token_id: token_id.clone().into(),
+                policy: MintingPolicy::new().into(),
+                to: to.to_vec(),
+            })
+        }));
+    }
+    Ok(initials)
+}
+
+impl Snapshot {
+    /// Initial funds for each voting key, equal to its voting power
+    pub fn to_block0_initials(&self, discrimination: Discrimination) -> Vec<InitialUTxO> {
+        self.inner
+            .iter()
+            .map(|(vk, entry)| InitialUTxO {
+                address: account_address(vk, discrimination),
+                value: entry.hir.voting_power,
+            })
+            .collect::<Vec<_>>()
+    }
+
+    /// See [`voters_to_block0_initials`]
+    pub fn to_block0_token_initials(
+        &self,
+        discrimination: Discrimination,
+        voting_tokens: &HashMap<VotingGroup, TokenIdentifier>,
+    ) -> Result<Vec<Initial>, Error> {
+        voters_to_block0_initials(&self.to_voter_hir(), discrimination, voting_tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jormungandr_lib::interfaces;
+    use std::str::FromStr;
+    use test_strategy::proptest;
+
+    fn token(n: u8) -> TokenIdentifier {
+        TokenIdentifier::from_str(&format!("{}.{}", hex::encode([n; 28]), hex::encode([n])))
+            .unwrap()
+    }
+
+    fn voter(key: u8, group: &str, voting_power: u64) -> VoterHIR {
+        VoterHIR {
+            voting_key: Identifier::from_hex(&hex::encode([key; 32])).unwrap(),
+            voting_group: group.to_string(),
+            voting_power: voting_power.into(),
+        }
+    }
+
+    #[proptest]
+    fn test_initials_match_voting_power(snapshot: Snapshot) {
+        let initials = snapshot.to_block0_initials(Discrimination::Production);
+        assert_eq!(initials.len(), snapshot.voting_keys().count());
+        assert_eq!(
+            initials.iter().map(|i| u64::from(i.value)).sum::<u64>(),
+            snapshot
+                .to_voter_hir()
+                .iter()
+                .map(|hir| u64::from(hir.voting_power))
+                .sum::<u64>()
+        );
+    }
+
+    #[test]
+    fn test_token_initials_per_group() {
+        let voters = [
+            voter(0, "direct", 10),
+            voter(1, "rep", 20),
+            voter(2, "direct", 30),
+        ];
+        let tokens = [
+            ("direct".to_string(), token(0)),
+            ("rep".to_string(), token(1)),
+        ]
+        .into_iter()
+        .collect();
+
+        let initials =
+            voters_to_block0_initials(&voters, Discrimination::Production, &tokens).unwrap();
+        assert_eq!(initials.len(), 2);
+        let mints = initials
+            .into_iter()
+            .map(|initial| match initial {
+                Initial::Token(mint) => mint,
+                _ => panic!("expected a token initial"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            mints[0].token_id,
+            interfaces::TokenIdentifier::from(token(0))
+        );
+        assert_eq!(
+            mints[0]
+                .to
+                .iter()
+                .map(|d| u64::from(d.value))
+                .collect::<Vec<_>>(),
+            vec![10, 30]
+        );
+        assert_eq!(
+            mints[1].token_id,
+            interfaces::TokenIdentifier::from(token(1))
+        );
+        assert_eq!(mints[1].to.len(), 1);
+    }
+
+    #[test]
+    fn test_token_initials_are_split() {
+        let voters = (0..=MAX_TOKEN_DESTINATIONS as u8)
+            .map(|key| voter(key, "direct", 1))
+            .chain([voter(0, "rep", 1)])
+            .collect::<Vec<_>>();
+        let tokens = [
+            ("direct".to_string(), token(0)),
+            ("rep".to_string(), token(1)),
+        ]
+        .into_iter()
+        .collect();
+
+        let initials =
+            voters_to_block0_initials(&voters, Discrimination::Production, &tokens).unwrap();
+        let mints = initials
+            .into_iter()
+            .map(|initial| match initial {
+                Initial::Token(mint) => (mint.token_id, mint.to.len()),
+                _ => panic!("expected a token initial"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            mints,
+            vec![
+                (
+                    interfaces::TokenIdentifier::from(token(0)),
+                    MAX_TOKEN_DESTINATIONS
+                ),
+                (interfaces::TokenIdentifier::from(token(0)), 1),
+                (interfaces::TokenIdentifier::from(token(1)), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_token() {
+        let voters = [voter(0, "direct", 10), voter(1, "rep", 20)];
+        let tokens = [("direct".to_string(), token(0))].into_iter().collect();
+        assert!(matches!(
+            voters_to_block0_initials(&voters, Discrimination::Test, &tokens),
+            Err(Error::MissingToken(group)) if group == "rep"
+        ));
+    }
+}
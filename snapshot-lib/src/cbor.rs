@@ -0,0 +1,119 @@
+//! Compact binary encoding of snapshots and registrations, based on CBOR (RFC 8949).
+//!
+//! CBOR is self-describing, so it supports the untagged representation of [`Delegations`]
+//! (which relies on `deserialize_any`) unlike formats such as bincode.
+//! Encoded values are prefixed with the self-describe tag 55799, which can be used to tell
+//! them apart from json without further information (see [`is_cbor`]). CBOR produced by other
+//! tools may lack the tag, in which case [`is_json`] can be used instead.
+//!
+//! This can be used for [`RawSnapshot`], [`SnapshotInfo`], [`VoterHIR`] or any other
+//! serializable type in this crate.
+//!
+//! [`Delegations`]: crate::registration::Delegations
+//! [`RawSnapshot`]: crate::RawSnapshot
+//! [`SnapshotInfo`]: crate::SnapshotInfo
+//! [`VoterHIR`]: crate::VoterHIR
+use serde::{de::DeserializeOwned, Serialize};
+use std::io::{self, Read, Write};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("cbor encoding failed: {0}")]
+    Encode(#[from] ciborium::ser::Error<io::Error>),
+    #[error("cbor decoding failed: {0}")]
+    Decode(#[from] ciborium::de::Error<io::Error>),
+}
+
+/// Encoding of the self-describe tag 55799
+pub const SELF_DESCRIBE_TAG: [u8; 3] = [0xd9, 0xd9, 0xf7];
+
+/// Returns true if `bytes` starts with the CBOR self-describe tag
+pub fn is_cbor(bytes: &[u8]) -> bool {
+    bytes.starts_with(&SELF_DESCRIBE_TAG)
+}
+
+/// Returns true if the first non-whitespace byte of `bytes` starts a json array or object,
+/// which is never the case for CBOR encoded arrays or maps, tagged or not
+pub fn is_json(bytes: &[u8]) -> bool {
+    matches!(
+        bytes.iter().find(|b| !b.is_ascii_whitespace()),
+        Some(b'[') | Some(b'{')
+    )
+}
+
+pub fn to_writer<W: Write, T: Serialize + ?Sized>(mut writer: W, value: &T) -> Result<(), Error> {
+    writer.write_all(&SELF_DESCRIBE_TAG)?;
+    Ok(ciborium::ser::into_writer(value, writer)?)
+}
+
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    to_writer(&mut bytes, value)?;
+    Ok(bytes)
+}
+
+/// Decode a value, with or without the self-describe tag
+pub fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+    let bytes = bytes.strip_prefix(&SELF_DESCRIBE_TAG[..]).unwrap_or(bytes);
+    Ok(ciborium::de::from_reader(bytes)?)
+}
+
+/// Decode a value, with or without the self-describe tag
+pub fn from_reader<R: Read, T: DeserializeOwned>(mut reader: R) -> Result<T, Error> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    from_slice(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RawSnapshot, SnapshotInfo, VoterHIR};
+    use proptest::{collection::vec, prelude::*};
+    use test_strategy::proptest;
+
+    #[proptest]
+    fn test_voter_hir_roundtrip(hir: VoterHIR) {
+        assert_eq!(from_slice::<VoterHIR>(&to_vec(&hir).unwrap()).unwrap(), hir);
+    }
+
+    #[proptest]
+    fn test_snapshot_info_roundtrip(
+        #[strategy(vec(any::<SnapshotInfo>(), 0..10))] info: Vec<SnapshotInfo>,
+    ) {
+        assert_eq!(
+            from_slice::<Vec<SnapshotInfo>>(&to_vec(&info).unwrap()).unwrap(),
+            info
+        );
+    }
+
+    #[proptest]
+    fn test_raw_snapshot_roundtrip(raw: RawSnapshot) {
+        let bytes = to_vec(&raw).unwrap();
+        assert!(is_cbor(&bytes));
+        assert_eq!(from_slice::<RawSnapshot>(&bytes).unwrap(), raw);
+    }
+
+    #[test]
+    fn test_untagged_is_accepted() {
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&vec![1u64, 2, 3], &mut bytes).unwrap();
+        assert!(!is_cbor(&bytes));
+        assert!(!is_json(&bytes));
+        assert_eq!(from_slice::<Vec<u64>>(&bytes).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_is_json() {
+        assert!(is_json(b" \n\t[]"));
+        assert!(is_json(b"{\"a\": 1}"));
+        assert!(!is_json(&to_vec(&vec![1u64, 2, 3]).unwrap()));
+        let mut map = Vec::new();
+        ciborium::ser::into_writer(&serde_json::json!({"a": 1}), &mut map).unwrap();
+        assert!(!is_json(&map));
+        assert!(!is_json(b""));
+    }
+}
@@ -0,0 +1,413 @@
+//! Parsing and verification of raw CIP-15 / CIP-36 registration metadata, as
+//! found on chain, into [`VotingRegistration`]s.
+
+use crate::registration::{
+    normalize_reward_address, Delegations, VotingPurpose, VotingRegistration,
+};
+use crate::CATALYST_VOTING_PURPOSE_TAG;
+use chain_crypto::{hash::Blake2b256, Ed25519, PublicKey, Signature, Verification};
+use ciborium::value::Value as Cbor;
+use jormungandr_lib::{crypto::account::Identifier, interfaces::Value};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+pub const REGISTRATION_METADATA_LABEL: u64 = 61284;
+pub const SIGNATURE_METADATA_LABEL: u64 = 61285;
+
+const DELEGATIONS_KEY: u64 = 1;
+const STAKE_PUBLIC_KEY_KEY: u64 = 2;
+const REWARD_ADDRESS_KEY: u64 = 3;
+const NONCE_KEY: u64 = 4;
+const VOTING_PURPOSE_KEY: u64 = 5;
+const SIGNATURE_KEY: u64 = 1;
+
+/// Metadata map, keeping only the entries with unsigned integer keys as no other key
+/// is defined by CIP-15 / CIP-36
+type Metadata = BTreeMap<u64, Cbor>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("malformed cbor: {0}")]
+    Cbor(#[from] ciborium::de::Error<std::io::Error>),
+    #[error("expected a metadata map")]
+    NotAMap,
+    #[error("missing field {0}")]
+    MissingField(u64),
+    #[error("invalid field {0}: {1}")]
+    InvalidField(u64, &'static str),
+    #[error("signature does not match the stake public key")]
+    InvalidSignature,
+}
+
+/// Raw metadata of a registration transaction, i.e. the CBOR encoded values
+/// associated to the registration (61284) and signature (61285) labels.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawRegistration {
+    #[serde(with = "hex_bytes")]
+    pub registration: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    pub signature: Vec<u8>,
+}
+
+/// A registration whose signature was checked against its stake key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cip36Registration {
+    pub delegations: Delegations,
+    pub stake_public_key: PublicKey<Ed25519>,
+    /// Raw bytes of the reward address, which are not checked to be a valid reward address
+    /// since the network of the address is checked when building the snapshot
+    pub reward_address: Vec<u8>,
+    pub nonce: u64,
+    /// 0 = Catalyst, assumed 0 for old legacy registrations
+    pub voting_purpose: VotingPurpose,
+}
+
+/// A registration that failed verification, along with the reason
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RejectedRegistration {
+    pub registration: RawRegistration,
+    pub reason: String,
+}
+
+impl Cip36Registration {
+    pub fn into_voting_registration(self, voting_power: Value) -> VotingRegistration {
+        VotingRegistration {
+            stake_public_key: format!("0x{}", hex::encode(self.stake_public_key.as_ref())),
+            voting_power,
+            reward_address: normalize_reward_address(format!(
+                "0x{}",
+                hex::encode(&self.reward_address)
+            )),
+            delegations: self.delegations,
+            voting_purpose: self.voting_purpose,
+            nonce: Some(self.nonce),
+            slot: None,
+        }
+    }
+}
+
+impl RawRegistration {
+    /// The payload signed by the stake key, that is the blake2b-256 hash of the
+    /// metadata map containing only the registration label.
+    fn signed_payload(&self) -> Blake2b256 {
+        // map(1), unsigned(61284)
+        let mut metadata = vec![0xa1, 0x19, 0xef, 0x64];
+        metadata.extend_from_slice(&self.registration);
+        Blake2b256::new(&metadata)
+    }
+
+    pub fn verify(&self) -> Result<Cip36Registration, Error> {
+        let registration = as_map(ciborium::de::from_reader(self.registration.as_slice())?)?;
+        let signature = as_map(ciborium::de::from_reader(self.signature.as_slice())?)?;
+
+        let stake_public_key = <PublicKey<Ed25519>>::from_binary(&bytes_field(
+            &registration,
+            STAKE_PUBLIC_KEY_KEY,
+        )?)
+        .map_err(|_| Error::InvalidField(STAKE_PUBLIC_KEY_KEY, "invalid ed25519 public key"))?;
+        let signature =
+            <Signature<Vec<u8>, Ed25519>>::from_binary(&bytes_field(&signature, SIGNATURE_KEY)?)
+                .map_err(|_| Error::InvalidField(SIGNATURE_KEY, "invalid ed25519 signature"))?;
+        let payload = self.signed_payload().as_ref().to_vec();
+        if !matches!(
+            signature.verify(&stake_public_key, &payload),
+            Verification::Success
+        ) {
+            return Err(Error::InvalidSignature);
+        }
+
+        let voting_purpose = match registration.get(&VOTING_PURPOSE_KEY) {
+            Some(_) => uint_field(&registration, VOTING_PURPOSE_KEY)?,
+            None => CATALYST_VOTING_PURPOSE_TAG,
+        };
+
+        Ok(Cip36Registration {
+            delegations: delegations_field(&registration)?,
+            stake_public_key,
+            reward_address: bytes_field(&registration, REWARD_ADDRESS_KEY)?,
+            nonce: uint_field(&registration, NONCE_KEY)?,
+            voting_purpose,
+        })
+    }
+}
+
+/// Verify raw registrations, each paired with the voting power of its stake key.
+///
+/// Returns the valid registrations and the rejected ones.
+pub fn verify_registrations(
+    registrations: impl IntoIterator<Item = (RawRegistration, Value)>,
+) -> (Vec<VotingRegistration>, Vec<RejectedRegistration>) {
+    let mut valid = Vec::new();
+    let mut rejected = Vec::new();
+    for (registration, voting_power) in registrations {
+        match registration.verify() {
+            Ok(reg) => valid.push(reg.into_voting_registration(voting_power)),
+            Err(e) => rejected.push(RejectedRegistration {
+                registration,
+                reason: e.to_string(),
+            }),
+        }
+    }
+    (valid, rejected)
+}
+
+fn as_map(value: Cbor) -> Result<Metadata, Error> {
+    match value {
+        Cbor::Map(entries) => Ok(entries
+            .into_iter()
+            .filter_map(|(key, value)| match key {
+                Cbor::Integer(key) => u64::try_from(key).ok().map(|key| (key, value)),
+                _ => None,
+            })
+            .collect()),
+        _ => Err(Error::NotAMap),
+    }
+}
+
+fn field(map: &Metadata, key: u64) -> Result<&Cbor, Error> {
+    map.get(&key).ok_or(Error::MissingField(key))
+}
+
+fn bytes_field(map: &Metadata, key: u64) -> Result<Vec<u8>, Error> {
+    match field(map, key)? {
+        Cbor::Bytes(bytes) => Ok(bytes.clone()),
+        _ => Err(Error::InvalidField(key, "expected bytes")),
+    }
+}
+
+fn uint_field(map: &Metadata, key: u64) -> Result<u64, Error> {
+    match field(map, key)? {
+        Cbor::Integer(n) => {
+            u64::try_from(*n).map_err(|_| Error::InvalidField(key, "expected an unsigned integer"))
+        }
+        _ => Err(Error::InvalidField(key, "expected an unsigned integer")),
+    }
+}
+
+fn voting_key(value: &Cbor) -> Result<Identifier, Error> {
+    match value {
+        Cbor::Bytes(bytes) => <PublicKey<Ed25519>>::from_binary(bytes)
+            .map(Identifier::from)
+            .map_err(|_| Error::InvalidField(DELEGATIONS_KEY, "invalid voting key")),
+        _ => Err(Error::InvalidField(
+            DELEGATIONS_KEY,
+            "expected a voting key",
+        )),
+    }
+}
+
+fn delegations_field(map: &Metadata) -> Result<Delegations, Error> {
+    match field(map, DELEGATIONS_KEY)? {
+        // CIP-15 legacy registration with a single voting key
+        legacy @ Cbor::Bytes(_) => Ok(Delegations::Legacy(voting_key(legacy)?)),
+        Cbor::Array(delegations) if !delegations.is_empty() => delegations
+            .iter()
+            .map(|delegation| match delegation {
+                Cbor::Array(pair) => match pair.as_slice() {
+                    [vk, Cbor::Integer(weight)] => Ok((
+                        voting_key(vk)?,
+                        u32::try_from(*weight).map_err(|_| {
+                            Error::InvalidField(DELEGATIONS_KEY, "weight is not a u32")
+                        })?,
+                    )),
+                    _ => Err(Error::InvalidField(
+                        DELEGATIONS_KEY,
+                        "expected a (voting key, weight) pair",
+                    )),
+                },
+                _ => Err(Error::InvalidField(
+                    DELEGATIONS_KEY,
+                    "expected a (voting key, weight) pair",
+                )),
+            })
+            .collect::<Result<_, _>>()
+            .map(Delegations::New),
+        _ => Err(Error::InvalidField(
+            DELEGATIONS_KEY,
+            "expected a voting key or a non empty list of delegations",
+        )),
+    }
+}
+
+pub(crate) mod hex_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&hex::encode(bytes))
+        } else {
+            serializer.serialize_bytes(bytes)
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            // accept both 0x and postgres' \x prefixes
+            let hex = String::deserialize(deserializer)?;
+            hex::decode(hex.trim_start_matches("0x").trim_start_matches("\\x"))
+                .map_err(|e| D::Error::custom(format!("invalid hex string: {}", e)))
+        } else {
+            <Vec<u8>>::deserialize(deserializer)
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use chain_crypto::SecretKey;
+
+    fn encode(map: &Metadata) -> Vec<u8> {
+        let map = Cbor::Map(
+            map.iter()
+                .map(|(key, value)| (Cbor::Integer((*key).into()), value.clone()))
+                .collect(),
+        );
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&map, &mut bytes).unwrap();
+        bytes
+    }
+
+    pub(crate) fn sign(registration: &Metadata, key: &SecretKey<Ed25519>) -> RawRegistration {
+        let mut raw = RawRegistration {
+            registration: encode(registration),
+            signature: Vec::new(),
+        };
+        let payload = raw.signed_payload().as_ref().to_vec();
+        let signature = key.sign(&payload);
+        raw.signature = encode(&BTreeMap::from([(
+            SIGNATURE_KEY,
+            Cbor::Bytes(signature.as_ref().to_vec()),
+        )]));
+        raw
+    }
+
+    pub(crate) fn registration(stake_key: &SecretKey<Ed25519>, delegations: Cbor) -> Metadata {
+        BTreeMap::from([
+            (DELEGATIONS_KEY, delegations),
+            (
+                STAKE_PUBLIC_KEY_KEY,
+                Cbor::Bytes(stake_key.to_public().as_ref().to_vec()),
+            ),
+            (REWARD_ADDRESS_KEY, Cbor::Bytes(vec![0xe1; 29])),
+            (NONCE_KEY, Cbor::Integer(42.into())),
+        ])
+    }
+
+    pub(crate) fn key(n: u8) -> SecretKey<Ed25519> {
+        SecretKey::from_binary(&[n; 32]).unwrap()
+    }
+
+    pub(crate) fn voting_key_bytes(n: u8) -> Cbor {
+        Cbor::Bytes(key(n).to_public().as_ref().to_vec())
+    }
+
+    #[test]
+    fn test_cip36_registration() {
+        let stake_key = key(0);
+        let mut reg = registration(
+            &stake_key,
+            Cbor::Array(vec![
+                Cbor::Array(vec![voting_key_bytes(1), Cbor::Integer(3.into())]),
+                Cbor::Array(vec![voting_key_bytes(2), Cbor::Integer(1.into())]),
+            ]),
+        );
+        reg.insert(VOTING_PURPOSE_KEY, Cbor::Integer(1.into()));
+
+        let parsed = sign(&reg, &stake_key).verify().unwrap();
+        assert_eq!(parsed.nonce, 42);
+        assert_eq!(parsed.voting_purpose, 1);
+        assert_eq!(parsed.stake_public_key, stake_key.to_public());
+        assert_eq!(
+            parsed.delegations,
+            Delegations::New(vec![
+                (key(1).to_public().into(), 3),
+                (key(2).to_public().into(), 1)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_legacy_registration() {
+        let stake_key = key(0);
+        let reg = registration(&stake_key, voting_key_bytes(1));
+
+        let parsed = sign(&reg, &stake_key).verify().unwrap();
+        assert_eq!(parsed.voting_purpose, CATALYST_VOTING_PURPOSE_TAG);
+        assert_eq!(
+            parsed.delegations,
+            Delegations::Legacy(key(1).to_public().into())
+        );
+    }
+
+    #[test]
+    fn test_wrong_signer_is_rejected() {
+        let reg = registration(&key(0), voting_key_bytes(1));
+        assert!(matches!(
+            sign(&reg, &key(1)).verify(),
+            Err(Error::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_tampered_registration_is_rejected() {
+        let stake_key = key(0);
+        let reg = registration(&stake_key, voting_key_bytes(1));
+        let mut raw = sign(&reg, &stake_key);
+
+        let mut tampered = reg;
+        tampered.insert(NONCE_KEY, Cbor::Integer(43.into()));
+        raw.registration = encode(&tampered);
+
+        let (valid, rejected) = verify_registrations([(raw, Value::from(1))]);
+        assert!(valid.is_empty());
+        assert_eq!(rejected.len(), 1);
+    }
+
+    #[test]
+    fn test_reward_address_network() {
+        let stake_key = key(0);
+        let mut reg = registration(&stake_key, voting_key_bytes(1));
+        let mut testnet_address = vec![0xe0];
+        testnet_address.extend_from_slice(&[0xab; 28]);
+        reg.insert(REWARD_ADDRESS_KEY, Cbor::Bytes(testnet_address));
+        let registration = sign(&reg, &stake_key)
+            .verify()
+            .unwrap()
+            .into_voting_registration(Value::from(1));
+        assert!(registration.reward_address.starts_with("stake_test1"));
+    }
+
+    #[test]
+    fn test_malformed_reward_address_is_kept() {
+        let stake_key = key(0);
+        let mut reg = registration(&stake_key, voting_key_bytes(1));
+        reg.insert(REWARD_ADDRESS_KEY, Cbor::Bytes(vec![0x01; 57]));
+        let registration = sign(&reg, &stake_key)
+            .verify()
+            .unwrap()
+            .into_voting_registration(Value::from(1));
+        assert_eq!(
+            registration.reward_address,
+            format!("0x{}", hex::encode([0x01; 57]))
+        );
+    }
+
+    #[test]
+    fn test_missing_nonce_is_rejected() {
+        let stake_key = key(0);
+        let mut reg = registration(&stake_key, voting_key_bytes(1));
+        reg.remove(&NONCE_KEY);
+        assert!(matches!(
+            sign(&reg, &stake_key).verify(),
+            Err(Error::MissingField(NONCE_KEY))
+        ));
+    }
+}
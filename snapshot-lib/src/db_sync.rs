@@ -0,0 +1,337 @@
+//! Offline import of registrations from CSV exports of db-sync tables.
+//!
+//! Three exports are expected, each with a header row and binary columns encoded in hex
+//! (with either the `\x` or `0x` prefix), as produced by `psql`'s `\copy (...) to ... with csv header`:
+//!
+//! * registration metadata, with columns `tx_id`, `key`, `bytes` and `slot_no`:
+//!   ```sql
+//!   SELECT tx_metadata.tx_id, tx_metadata.key, tx_metadata.bytes, block.slot_no
+//!   FROM tx_metadata
+//!   JOIN tx ON tx.id = tx_metadata.tx_id
+//!   JOIN block ON block.id = tx.block_id
+//!   WHERE tx_metadata.key IN (61284, 61285)
+//!   ```
+//! * stake addresses, with columns `id` and `hash_raw`:
+//!   ```sql
+//!   SELECT id, hash_raw FROM stake_address
+//!   ```
+//! * UTxO stake, with columns `stake_address_id`, `value`, `slot_no` (the slot the output was
+//!   created in) and `spent_slot_no` (empty if the output is unspent):
+//!   ```sql
+//!   SELECT tx_out.stake_address_id, tx_out.value, block.slot_no, spent_block.slot_no AS spent_slot_no
+//!   FROM tx_out
+//!   JOIN tx ON tx.id = tx_out.tx_id
+//!   JOIN block ON block.id = tx.block_id
+//!   LEFT JOIN tx_in ON tx_in.tx_out_id = tx_out.tx_id AND tx_in.tx_out_index = tx_out.index
+//!   LEFT JOIN tx AS spent_tx ON spent_tx.id = tx_in.tx_in_id
+//!   LEFT JOIN block AS spent_block ON spent_block.id = spent_tx.block_id
+//!   WHERE tx_out.stake_address_id IS NOT NULL
+//!   ```
+//!
+//! The voting power of each registration is computed from the exported stake rather than taken
+//! on trust: it's the value of the outputs delegated to the stake key which, at the requested
+//! slot, were already created and not yet spent.
+//!
+//! For the same reason, registrations included in a block after the requested slot are
+//! rejected: their stake key may not hold the computed stake anymore by the time they were
+//! submitted, and a snapshot at a slot cannot contain registrations from its future.
+//! Registrations without a slot are imported.
+use crate::{
+    cip36::{
+        hex_bytes, RawRegistration, RejectedRegistration, REGISTRATION_METADATA_LABEL,
+        SIGNATURE_METADATA_LABEL,
+    },
+    RawSnapshot,
+};
+use chain_crypto::hash::Blake2b224;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Read,
+};
+use thiserror::Error;
+
+/// Length of a stake credential, i.e. the blake2b-224 hash of a stake public key
+const CREDENTIAL_LEN: usize = 28;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    #[error("stake address {0} is not a valid reward address")]
+    InvalidStakeAddress(u64),
+    #[error("voting power overflow")]
+    Overflow,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataRow {
+    tx_id: u64,
+    key: u64,
+    #[serde(with = "hex_bytes")]
+    bytes: Vec<u8>,
+    slot_no: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StakeAddressRow {
+    id: u64,
+    #[serde(with = "hex_bytes")]
+    hash_raw: Vec<u8>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UtxoRow {
+    stake_address_id: u64,
+    value: u64,
+    slot_no: u64,
+    spent_slot_no: Option<u64>,
+}
+
+/// Registrations imported from db-sync exports
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbSyncImport {
+    /// Registrations with a valid signature, with their voting power at the requested slot
+    pub registrations: RawSnapshot,
+    /// Registrations that failed verification, are missing part of their metadata or were
+    /// included in a block after the requested slot
+    pub rejected: Vec<RejectedRegistration>,
+}
+
+/// Build a [`RawSnapshot`] from db-sync exports, computing voting power at `slot`.
+/// See the module documentation for the expected format of each export.
+pub fn import_registrations(
+    metadata: impl Read,
+    stake_addresses: impl Read,
+    utxos: impl Read,
+    slot: u64,
+) -> Result<DbSyncImport, Error> {
+    let stake = stake_by_credential(stake_addresses, stake_at_slot(utxos, slot)?)?;
+
+    // registration and signature metadata of each transaction, with its slot
+    let mut txs = BTreeMap::<u64, (Option<Vec<u8>>, Option<Vec<u8>>, Option<u64>)>::new();
+    for row in csv::Reader::from_reader(metadata).deserialize() {
+        let row: MetadataRow = row?;
+        if row.key != REGISTRATION_METADATA_LABEL && row.key != SIGNATURE_METADATA_LABEL {
+            continue;
+        }
+        let tx = txs.entry(row.tx_id).or_default();
+        if row.key == REGISTRATION_METADATA_LABEL {
+            tx.0 = Some(row.bytes);
+        } else {
+            tx.1 = Some(row.bytes);
+        }
+        tx.2 = row.slot_no;
+    }
+
+    let mut registrations = Vec::new();
+    let mut rejected = Vec::new();
+    for (registration, signature, slot_no) in txs.into_values() {
+        let missing = match (&registration, &signature) {
+            (Some(_), Some(_)) => None,
+            (None, _) => Some("missing registration metadata"),
+            (_, None) => Some("missing signature metadata"),
+        };
+        let raw = RawRegistration {
+            registration: registration.unwrap_or_default(),
+            signature: signature.unwrap_or_default(),
+        };
+        if let Some(reason) = missing {
+            rejected.push(RejectedRegistration {
+                registration: raw,
+                reason: reason.to_string(),
+            });
+            continue;
+        }
+        if let Some(slot_no) = slot_no.filter(|slot_no| *slot_no > slot) {
+            rejected.push(RejectedRegistration {
+                registration: raw,
+                reason: format!("included in slot {} after slot {}", slot_no, slot),
+            });
+            continue;
+        }
+        match raw.verify() {
+            Ok(verified) => {
+                let credential = Blake2b224::new(verified.stake_public_key.as_ref());
+                let voting_power = stake
+                    .get::<[u8]>(credential.as_ref())
+                    .copied()
+                    .unwrap_or_default();
+                let mut registration = verified.into_voting_registration(voting_power.into());
+                registration.slot = slot_no;
+                registrations.push(registration);
+            }
+            Err(e) => rejected.push(RejectedRegistration {
+                registration: raw,
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    Ok(DbSyncImport {
+        registrations: registrations.into(),
+        rejected,
+    })
+}
+
+/// Stake of each stake address id held in outputs that exist at `slot`
+fn stake_at_slot(utxos: impl Read, slot: u64) -> Result<HashMap<u64, u64>, Error> {
+    let mut stake = HashMap::new();
+    for row in csv::Reader::from_reader(utxos).deserialize() {
+        let row: UtxoRow = row?;
+        if row.slot_no > slot || row.spent_slot_no.map_or(false, |spent| spent <= slot) {
+            continue;
+        }
+        let entry = stake.entry(row.stake_address_id).or_insert(0u64);
+        *entry = entry.checked_add(row.value).ok_or(Error::Overflow)?;
+    }
+    Ok(stake)
+}
+
+/// Stake of each stake credential, found after the header byte of a reward address
+fn stake_by_credential(
+    stake_addresses: impl Read,
+    mut stake: HashMap<u64, u64>,
+) -> Result<HashMap<Vec<u8>, u64>, Error> {
+    let mut by_credential = HashMap::new();
+    for row in csv::Reader::from_reader(stake_addresses).deserialize() {
+        let row: StakeAddressRow = row?;
+        if row.hash_raw.len() != 1 + CREDENTIAL_LEN {
+            return Err(Error::InvalidStakeAddress(row.id));
+        }
+        let value = stake.remove(&row.id).unwrap_or_default();
+        let entry = by_credential
+            .entry(row.hash_raw[1..].to_vec())
+            .or_insert(0u64);
+        *entry = entry.checked_add(value).ok_or(Error::Overflow)?;
+    }
+    Ok(by_credential)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cip36::tests::{key, registration, sign, voting_key_bytes};
+    use crate::registration::Delegations;
+
+    fn metadata_csv(rows: &[(u64, u64, &[u8], u64)]) -> String {
+        let mut csv = "tx_id,key,bytes,slot_no\n".to_string();
+        for (tx_id, key, bytes, slot_no) in rows {
+            csv.push_str(&format!(
+                "{},{},\\x{},{}\n",
+                tx_id,
+                key,
+                hex::encode(bytes),
+                slot_no
+            ));
+        }
+        csv
+    }
+
+    #[test]
+    fn test_import_registrations() {
+        let stake_key = key(0);
+        let raw = sign(&registration(&stake_key, voting_key_bytes(1)), &stake_key);
+        let metadata = metadata_csv(&[
+            (
+                1,
+                REGISTRATION_METADATA_LABEL,
+                raw.registration.as_slice(),
+                10,
+            ),
+            (1, SIGNATURE_METADATA_LABEL, raw.signature.as_slice(), 10),
+            // a registration without signature
+            (
+                2,
+                REGISTRATION_METADATA_LABEL,
+                raw.registration.as_slice(),
+                11,
+            ),
+        ]);
+
+        let credential = Blake2b224::new(stake_key.to_public().as_ref());
+        let stake_addresses = format!(
+            "id,hash_raw\n7,\\xe1{}\n8,\\xe1{}\n",
+            hex::encode(&credential),
+            hex::encode([0xab; 28])
+        );
+        // only the first output exists at slot 20
+        let utxos = "stake_address_id,value,slot_no,spent_slot_no\n\
+            7,100,5,\n\
+            7,50,5,15\n\
+            7,30,25,\n\
+            8,1000,5,\n";
+
+        let import = import_registrations(
+            metadata.as_bytes(),
+            stake_addresses.as_bytes(),
+            utxos.as_bytes(),
+            20,
+        )
+        .unwrap();
+        let registrations = import.registrations.0;
+        assert_eq!(registrations.len(), 1);
+        assert_eq!(registrations[0].voting_power, 100.into());
+        assert_eq!(registrations[0].slot, Some(10));
+        assert_eq!(
+            registrations[0].delegations,
+            Delegations::Legacy(key(1).to_public().into())
+        );
+        assert_eq!(import.rejected.len(), 1);
+        assert_eq!(import.rejected[0].reason, "missing signature metadata");
+    }
+
+    #[test]
+    fn test_registrations_after_slot_are_rejected() {
+        let stake_key = key(0);
+        let raw = sign(&registration(&stake_key, voting_key_bytes(1)), &stake_key);
+        let metadata = metadata_csv(&[
+            (
+                1,
+                REGISTRATION_METADATA_LABEL,
+                raw.registration.as_slice(),
+                20,
+            ),
+            (1, SIGNATURE_METADATA_LABEL, raw.signature.as_slice(), 20),
+            (
+                2,
+                REGISTRATION_METADATA_LABEL,
+                raw.registration.as_slice(),
+                21,
+            ),
+            (2, SIGNATURE_METADATA_LABEL, raw.signature.as_slice(), 21),
+        ]);
+
+        let import = import_registrations(
+            metadata.as_bytes(),
+            "id,hash_raw
+"
+            .as_bytes(),
+            "stake_address_id,value,slot_no,spent_slot_no
+"
+            .as_bytes(),
+            20,
+        )
+        .unwrap();
+        // a registration in the requested slot is imported
+        assert_eq!(import.registrations.0.len(), 1);
+        assert_eq!(import.registrations.0[0].slot, Some(20));
+        assert_eq!(import.rejected.len(), 1);
+        assert_eq!(import.rejected[0].registration, raw);
+        assert_eq!(
+            import.rejected[0].reason,
+            "included in slot 21 after slot 20"
+        );
+    }
+
+    #[test]
+    fn test_invalid_stake_address() {
+        let res = import_registrations(
+            "tx_id,key,bytes,slot_no\n".as_bytes(),
+            "id,hash_raw\n1,\\xe1ab\n".as_bytes(),
+            "stake_address_id,value,slot_no,spent_slot_no\n".as_bytes(),
+            0,
+        );
+        assert!(matches!(res, Err(Error::InvalidStakeAddress(1))));
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{
+    registration::{MainnetRewardAddress, MainnetStakeAddress},
+    voting_group::VotingGroupAssigner,
+    Error, Fraction, RawSnapshot, Snapshot, SnapshotInfo, SnapshotOptions, VotingGroup,
+};
+use jormungandr_lib::{crypto::account::Identifier, interfaces::Value};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// Change in the value a single stake key contributes to a voting key.
+/// A value of 0 means the stake key was not contributing in that snapshot.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContributionDiff {
+    pub stake_public_key: MainnetStakeAddress,
+    pub reward_address: MainnetRewardAddress,
+    pub old_value: u64,
+    pub new_value: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VoterDiff {
+    #[serde(with = "crate::voter_hir::serde")]
+    pub voting_key: Identifier,
+    pub change: Change,
+    pub old_voting_group: Option<VotingGroup>,
+    pub new_voting_group: Option<VotingGroup>,
+    pub old_voting_power: Option<Value>,
+    pub new_voting_power: Option<Value>,
+    pub voting_power_delta: i128,
+    /// Only stake keys whose contribution changed are listed
+    pub contributions: Vec<ContributionDiff>,
+}
+
+/// Differences between two snapshots, ordered by voting key.
+/// Voting keys whose entry is identical in both snapshots are not reported.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotDiff {
+    pub voters: Vec<VoterDiff>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.voters.is_empty()
+    }
+
+    pub fn added(&self) -> impl Iterator<Item = &VoterDiff> {
+        self.voters.iter().filter(|v| v.change == Change::Added)
+    }
+
+    pub fn removed(&self) -> impl Iterator<Item = &VoterDiff> {
+        self.voters.iter().filter(|v| v.change == Change::Removed)
+    }
+
+    pub fn modified(&self) -> impl Iterator<Item = &VoterDiff> {
+        self.voters.iter().filter(|v| v.change == Change::Modified)
+    }
+}
+
+fn contributions_diff(
+    old: Option<&SnapshotInfo>,
+    new: Option<&SnapshotInfo>,
+) -> Vec<ContributionDiff> {
+    // Multiple contributions from the same stake key to the same voting key are merged
+    let by_stake_key = |info: Option<&SnapshotInfo>| {
+        info.into_iter()
+            .flat_map(|info| info.contributions.iter())
+            .fold(BTreeMap::new(), |mut acc: BTreeMap<_, (_, u64)>, c| {
+                let entry = acc
+                    .entry(c.stake_public_key.clone())
+                    .or_insert_with(|| (c.reward_address.clone(), 0));
+                entry.1 += c.value;
+                acc
+            })
+    };
+    let old = by_stake_key(old);
+    let new = by_stake_key(new);
+
+    old.keys()
+        .chain(new.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|stake_public_key| {
+            let (old, new) = (old.get(stake_public_key), new.get(stake_public_key));
+            let old_value = old.map_or(0, |(_, value)| *value);
+            let new_value = new.map_or(0, |(_, value)| *value);
+            let (reward_address, _) = new.or(old)?;
+            (old_value != new_value).then(|| ContributionDiff {
+                stake_public_key: stake_public_key.clone(),
+                reward_address: reward_address.clone(),
+                old_value,
+                new_value,
+            })
+        })
+        .collect()
+}
+
+/// Compare two lists of [`SnapshotInfo`], as produced by [`Snapshot::to_full_snapshot_info`].
+/// Entries are matched by voting key.
+pub fn diff_snapshot_info(old: &[SnapshotInfo], new: &[SnapshotInfo]) -> SnapshotDiff {
+    let by_key = |infos: &[SnapshotInfo]| {
+        infos
+            .iter()
+            .map(|info| (info.hir.voting_key.clone(), info.clone()))
+            .collect::<BTreeMap<_, _>>()
+    };
+    let old = by_key(old);
+    let new = by_key(new);
+
+    let voters = old
+        .keys()
+        .chain(new.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|voting_key| {
+            let (old, new) = (old.get(voting_key), new.get(voting_key));
+            if old == new {
+                return None;
+            }
+            let change = match (old, new) {
+                (None, _) => Change::Added,
+                (_, None) => Change::Removed,
+                _ => Change::Modified,
+            };
+            let old_voting_power = old.map(|info| info.hir.voting_power);
+            let new_voting_power = new.map(|info| info.hir.voting_power);
+            let voting_power_delta = new_voting_power.map_or(0, |vp| i128::from(u64::from(vp)))
+                - old_voting_power.map_or(0, |vp| i128::from(u64::from(vp)));
+
+            Some(VoterDiff {
+                voting_key: voting_key.clone(),
+                change,
+                old_voting_group: old.map(|info| info.hir.voting_group.clone()),
+                new_voting_group: new.map(|info| info.hir.voting_group.clone()),
+                old_voting_power,
+                new_voting_power,
+                voting_power_delta,
+                contributions: contributions_diff(old, new),
+            })
+        })
+        .collect();
+
+    SnapshotDiff { voters }
+}
+
+/// Process two raw snapshots with the same parameters and compare the results.
+pub fn diff_raw_snapshots(
+    old: RawSnapshot,
+    new: RawSnapshot,
+    stake_threshold: Value,
+    cap: Fraction,
+    voting_group_assigner: &impl VotingGroupAssigner,
+    options: &SnapshotOptions,
+) -> Result<SnapshotDiff, Error> {
+    let (old, _) = Snapshot::from_raw_snapshot_with_options(
+        old,
+        stake_threshold,
+        cap,
+        voting_group_assigner,
+        options,
+    )?;
+    let (new, _) = Snapshot::from_raw_snapshot_with_options(
+        new,
+        stake_threshold,
+        cap,
+        voting_group_assigner,
+        options,
+    )?;
+    Ok(old.diff(&new))
+}
+
+impl Snapshot {
+    pub fn diff(&self, other: &Snapshot) -> SnapshotDiff {
+        diff_snapshot_info(
+            &self.to_full_snapshot_info(),
+            &other.to_full_snapshot_info(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{KeyContribution, VoterHIR};
+    use test_strategy::proptest;
+
+    fn voting_key(n: u8) -> Identifier {
+        Identifier::from_hex(&hex::encode([n; 32])).unwrap()
+    }
+
+    fn info(key: u8, group: &str, contributions: &[(&str, u64)]) -> SnapshotInfo {
+        let contributions = contributions
+            .iter()
+            .map(|(stake_key, value)| KeyContribution {
+                stake_public_key: stake_key.to_string(),
+                reward_address: stake_key.to_string(),
+                value: *value,
+                weight: None,
+                effective_value: None,
+            })
+            .collect::<Vec<_>>();
+        let voting_power = contributions.iter().map(|c| c.value).sum::<u64>().into();
+        SnapshotInfo {
+            hir: VoterHIR {
+                voting_key: voting_key(key),
+                voting_group: group.to_string(),
+                voting_power,
+            },
+            contributions,
+            raw_voting_power: voting_power,
+            pre_cap_voting_power: voting_power,
+            cap_reduction: 0.into(),
+        }
+    }
+
+    #[proptest]
+    fn test_diff_with_itself_is_empty(snapshot: Snapshot) {
+        assert!(snapshot.diff(&snapshot).is_empty());
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = vec![
+            info(0, "direct", &[("a", 10)]),
+            info(1, "direct", &[("b", 20), ("c", 5)]),
+            info(2, "direct", &[("d", 1)]),
+        ];
+        let new = vec![
+            info(1, "rep", &[("b", 20), ("c", 7), ("e", 3)]),
+            info(2, "direct", &[("d", 1)]),
+            info(3, "direct", &[("f", 4)]),
+        ];
+
+        let diff = diff_snapshot_info(&old, &new);
+        assert_eq!(diff.voters.len(), 3);
+        assert_eq!(
+            diff.removed().map(|v| &v.voting_key).collect::<Vec<_>>(),
+            vec![&voting_key(0)]
+        );
+        assert_eq!(
+            diff.added().map(|v| &v.voting_key).collect::<Vec<_>>(),
+            vec![&voting_key(3)]
+        );
+
+        let modified = diff.modified().collect::<Vec<_>>();
+        assert_eq!(modified.len(), 1);
+        assert_eq!(modified[0].voting_key, voting_key(1));
+        assert_eq!(modified[0].old_voting_group.as_deref(), Some("direct"));
+        assert_eq!(modified[0].new_voting_group.as_deref(), Some("rep"));
+        assert_eq!(modified[0].voting_power_delta, 5);
+        assert_eq!(
+            modified[0].contributions,
+            vec![
+                ContributionDiff {
+                    stake_public_key: "c".to_string(),
+                    reward_address: "c".to_string(),
+                    old_value: 5,
+                    new_value: 7,
+                },
+                ContributionDiff {
+                    stake_public_key: "e".to_string(),
+                    reward_address: "e".to_string(),
+                    old_value: 0,
+                    new_value: 3,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! Step by step derivation of the voting power of a key in a snapshot, from the value
+//! contributed by each stake key to the final voting power after transformations and the cap.
+use crate::{
+    apportionment::largest_remainder,
+    registration::{MainnetRewardAddress, MainnetStakeAddress},
+    Snapshot, SnapshotInfo, VotingGroup,
+};
+use jormungandr_lib::{crypto::account::Identifier, interfaces::Value};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContributionExplanation {
+    pub stake_public_key: MainnetStakeAddress,
+    pub reward_address: MainnetRewardAddress,
+    /// Value in the registration
+    pub value: u64,
+    /// Share of the raw voting power of the key contributed by this stake key, in basis
+    /// points (1/100 of a percent), rounded down
+    pub share_bps: u64,
+    /// Voting power effectively contributed after transformations and the cap
+    pub effective_value: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Explanation {
+    #[serde(with = "crate::voter_hir::serde")]
+    pub voting_key: Identifier,
+    pub voting_group: VotingGroup,
+    pub contributions: Vec<ContributionExplanation>,
+    /// Sum of the values of all contributions
+    pub raw_voting_power: Value,
+    /// Voting power after transformations, right before the voting power cap.
+    /// Not available for snapshots produced before it was recorded.
+    pub pre_cap_voting_power: Option<Value>,
+    pub cap_reduction: Option<Value>,
+    pub voting_power: Value,
+}
+
+const BPS_PER_UNIT: u128 = 10_000;
+
+impl Explanation {
+    pub fn new(info: &SnapshotInfo) -> Self {
+        let raw = info.contributions.iter().map(|c| c.value).sum::<u64>();
+        // snapshots produced before effective values were recorded can be explained as well
+        let computed = largest_remainder(
+            info.hir.voting_power.into(),
+            &info
+                .contributions
+                .iter()
+                .map(|c| c.value)
+                .collect::<Vec<_>>(),
+        );
+        let contributions = info
+            .contributions
+            .iter()
+            .zip(computed)
+            .map(|(c, computed)| ContributionExplanation {
+                stake_public_key: c.stake_public_key.clone(),
+                reward_address: c.reward_address.clone(),
+                value: c.value,
+                share_bps: if raw == 0 {
+                    0
+                } else {
+                    // at most BPS_PER_UNIT, since the value is part of the raw voting power
+                    (c.value as u128 * BPS_PER_UNIT / raw as u128) as u64
+                },
+                effective_value: c.effective_value.unwrap_or(computed),
+            })
+            .collect();
+        let has_cap_info = info.has_cap_info();
+        Self {
+            voting_key: info.hir.voting_key.clone(),
+            voting_group: info.hir.voting_group.clone(),
+            contributions,
+            raw_voting_power: raw.into(),
+            pre_cap_voting_power: has_cap_info.then(|| info.pre_cap_voting_power),
+            cap_reduction: has_cap_info.then(|| info.cap_reduction),
+            voting_power: info.hir.voting_power,
+        }
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = u64::from(self.raw_voting_power);
+        writeln!(f, "voting key: {}", self.voting_key.to_hex())?;
+        writeln!(f, "voting group: {}", self.voting_group)?;
+        writeln!(f, "contributions:")?;
+        for c in &self.contributions {
+            writeln!(
+                f,
+                "  {} (reward address {}): {} ({}.{:02}% of the raw voting power), effective {}",
+                c.stake_public_key,
+                c.reward_address,
+                c.value,
+                c.share_bps / 100,
+                c.share_bps % 100,
+                c.effective_value
+            )?;
+        }
+        writeln!(f, "raw voting power (sum of contributions): {}", raw)?;
+        match (self.pre_cap_voting_power, self.cap_reduction) {
+            (Some(pre_cap), Some(cap_reduction)) => {
+                let pre_cap = u64::from(pre_cap);
+                writeln!(
+                    f,
+                    "after transformations: {} ({:+})",
+                    pre_cap,
+                    pre_cap as i128 - raw as i128
+                )?;
+                writeln!(f, "voting power cap: -{}", u64::from(cap_reduction))?;
+            }
+            _ => writeln!(
+                f,
+                "transformations and voting power cap: not recorded in this snapshot"
+            )?,
+        }
+        write!(
+            f,
+            "final voting power (sum of effective contributions): {}",
+            self.voting_power
+        )
+    }
+}
+
+impl Snapshot {
+    /// Explain how the voting power of `voting_key` was derived, if it's in the snapshot
+    pub fn explain(&self, voting_key: &Identifier) -> Option<Explanation> {
+        self.inner.get(voting_key).map(Explanation::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        registration::{Delegations, VotingRegistration},
+        Fraction,
+    };
+    use test_strategy::proptest;
+
+    #[test]
+    fn test_cap_is_explained() {
+        let whale = Identifier::from_hex(&hex::encode([0; 32])).unwrap();
+        let reg = |stake_key: &str, vk: &Identifier, voting_power: u64| VotingRegistration {
+            stake_public_key: stake_key.to_string(),
+            voting_power: voting_power.into(),
+            reward_address: String::new(),
+            delegations: Delegations::Legacy(vk.clone()),
+            voting_purpose: 0,
+            nonce: None,
+            slot: None,
+        };
+        let mut raw = vec![reg("a", &whale, 600), reg("b", &whale, 400)];
+        for i in 1..=3u8 {
+            let vk = Identifier::from_hex(&hex::encode([i; 32])).unwrap();
+            raw.push(reg("c", &vk, 100));
+        }
+        let snapshot = Snapshot::from_raw_snapshot(
+            raw.into(),
+            0.into(),
+            Fraction::new(1u64, 4u64),
+            &|_vk: &Identifier| String::new(),
+        )
+        .unwrap();
+
+        let explanation = snapshot.explain(&whale).unwrap();
+        assert_eq!(explanation.raw_voting_power, 1000.into());
+        assert_eq!(explanation.pre_cap_voting_power, Some(1000.into()));
+        assert_eq!(explanation.voting_power, 100.into());
+        assert_eq!(explanation.cap_reduction, Some(900.into()));
+        assert_eq!(
+            explanation
+                .contributions
+                .iter()
+                .map(|c| (c.share_bps, c.effective_value))
+                .collect::<Vec<_>>(),
+            vec![(6000, 60), (4000, 40)]
+        );
+        assert!(explanation
+            .to_string()
+            .contains("600 (60.00% of the raw voting power), effective 60"));
+
+        // snapshots produced before the voting power before the cap was recorded
+        let mut old = snapshot
+            .to_full_snapshot_info()
+            .into_iter()
+            .find(|info| info.hir.voting_key == whale)
+            .unwrap();
+        old.pre_cap_voting_power = 0.into();
+        old.cap_reduction = 0.into();
+        let explanation = Explanation::new(&old);
+        assert_eq!(explanation.pre_cap_voting_power, None);
+        assert_eq!(explanation.cap_reduction, None);
+        assert!(explanation.to_string().contains("not recorded"));
+    }
+
+    #[proptest]
+    fn test_effective_values_sum_to_voting_power(snapshot: Snapshot) {
+        for info in snapshot.to_full_snapshot_info() {
+            let explanation = Explanation::new(&info);
+            assert_eq!(
+                explanation
+                    .contributions
+                    .iter()
+                    .map(|c| c.effective_value)
+                    .sum::<u64>(),
+                u64::from(info.hir.voting_power)
+            );
+            assert_eq!(
+                u64::from(info.pre_cap_voting_power) - u64::from(info.cap_reduction),
+                u64::from(info.hir.voting_power)
+            );
+        }
+    }
+}
@@ -1,6 +1,7 @@
-use super::{Error, SnapshotInfo};
+use super::{Error, SnapshotInfo, VotingGroup};
 use fraction::{BigFraction, Fraction};
 use rust_decimal::prelude::ToPrimitive;
+use std::collections::BTreeMap;
 
 /// Calculates ceil(a / b) where a and b are integers with perfect precision
 #[inline]
@@ -28,7 +29,43 @@ fn calc_vp_to_remove(x: u64, tot: u64, threshold: Fraction) -> u64 {
     (num / denum).ceil().to_u64().unwrap()
 }
 
+/// Cap the voting power of each voter inside its voting group according to the threshold,
+/// as described in [`cap_group_voting_influence`].
+pub fn cap_voting_influence(
+    voters: Vec<SnapshotInfo>,
+    threshold: Fraction,
+) -> Result<Vec<SnapshotInfo>, Error> {
+    cap_voting_influence_per_group(voters, threshold, &BTreeMap::new())
+}
+
+/// Cap the voting power of each voter inside its voting group, using the threshold
+/// in `group_thresholds` for that group if present, or `default_threshold` otherwise.
+pub fn cap_voting_influence_per_group(
+    voters: Vec<SnapshotInfo>,
+    default_threshold: Fraction,
+    group_thresholds: &BTreeMap<VotingGroup, Fraction>,
+) -> Result<Vec<SnapshotInfo>, Error> {
+    let mut groups = BTreeMap::new();
+    for voter in voters {
+        groups
+            .entry(voter.hir.voting_group.clone())
+            .or_insert_with(Vec::new)
+            .push(voter);
+    }
+
+    let mut res = Vec::new();
+    for (group, voters) in groups {
+        let threshold = group_thresholds
+            .get(&group)
+            .copied()
+            .unwrap_or(default_threshold);
+        res.extend(cap_group_voting_influence(voters, threshold)?);
+    }
+    Ok(res)
+}
+
 /// Cap each individual voting power according to the threshold, if possible.
+/// All voters are expected to belong to the same voting group.
 ///
 /// Obviously, to cap each individual's influence to T, we need at east M = ceil(1/T) participants.
 /// If we have M or more participants, it's always possible to do it.
@@ -45,7 +82,7 @@ fn calc_vp_to_remove(x: u64, tot: u64, threshold: Fraction) -> u64 {
 ///       It's easy to see we need to repeat step 2.1 at most min(ceil(1/T), N) times.
 ///
 /// Complexity: O(NlogN + min(ceil(1/T), N))
-pub fn cap_voting_influence(
+fn cap_group_voting_influence(
     mut voters: Vec<SnapshotInfo>,
     threshold: Fraction,
 ) -> Result<Vec<SnapshotInfo>, Error> {
@@ -56,7 +93,7 @@ pub fn cap_voting_influence(
     }
 
     if Fraction::new(1u64, voters.len() as u64) > threshold {
-        return Err(Error::NotEnoughVoters);
+        return Err(Error::NotEnoughVoters(voters[0].hir.voting_group.clone()));
     }
     let mut tot = 0u64;
     // can't check for overflows with Iterator::sum()
@@ -180,6 +217,38 @@ mod tests {
         }
     }
 
+    #[proptest]
+    fn test_groups_are_capped_separately(
+        #[strategy(vec(any_with::<SnapshotInfo>(("direct".to_string(), DEFAULT_VP_STRATEGY)), 10..=10))]
+        direct: Vec<SnapshotInfo>,
+        #[strategy(vec(any_with::<SnapshotInfo>(("rep".to_string(), DEFAULT_VP_STRATEGY)), 2..=2))]
+        reps: Vec<SnapshotInfo>,
+    ) {
+        let voters = direct.into_iter().chain(reps).collect::<Vec<_>>();
+        // there are enough voters overall, but not enough representatives
+        let cap = Fraction::new(1u64, 10u64);
+        match cap_voting_influence(voters.clone(), cap) {
+            Err(Error::NotEnoughVoters(group)) => assert_eq!(group, "rep"),
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let group_caps = [("rep".to_string(), Fraction::new(1u64, 2u64))]
+            .into_iter()
+            .collect();
+        let res = cap_voting_influence_per_group(voters, cap, &group_caps).unwrap();
+        for (group, cap) in [("direct", cap), ("rep", Fraction::new(1u64, 2u64))] {
+            let vps = res
+                .iter()
+                .filter(|entry| entry.hir.voting_group == group)
+                .map(|entry| u64::from(entry.hir.voting_power))
+                .collect::<Vec<_>>();
+            let tot = vps.iter().sum::<u64>();
+            for v in vps {
+                assert!(Fraction::new(v, tot) <= cap);
+            }
+        }
+    }
+
     impl Arbitrary for SnapshotInfo {
         type Parameters = (String, VpRange);
         type Strategy = BoxedStrategy<Self>;
@@ -188,6 +257,9 @@ mod tests {
             any_with::<VoterHIR>(args)
                 .prop_map(|hir| Self {
                     contributions: Vec::new(),
+                    raw_voting_power: hir.voting_power,
+                    pre_cap_voting_power: hir.voting_power,
+                    cap_reduction: 0.into(),
                     hir,
                 })
                 .boxed()
@@ -1,22 +1,42 @@
+use apportionment::Apportionment;
 pub use fraction::Fraction;
 use jormungandr_lib::{crypto::account::Identifier, interfaces::Value};
 use registration::MainnetStakeAddress;
-use registration::{Delegations, MainnetRewardAddress, VotingRegistration};
+use registration::{MainnetRewardAddress, VotingPurpose, VotingRegistration};
+use reward_address::Network;
 use serde::{Deserialize, Serialize};
-use std::{borrow::Borrow, collections::BTreeMap, iter::Iterator, num::NonZeroU64};
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, BTreeSet},
+    iter::Iterator,
+};
+use stream::{ReportEntry, ReportReason, SnapshotBuilder};
 use thiserror::Error;
+use transform::{Transform, VotingPowerTransform};
 pub use voter_hir::VoterHIR;
 pub use voter_hir::VotingGroup;
-use voting_group::VotingGroupAssigner;
-
+use voting_group::{DelegationKinds, VoterInfo, VotingGroupAssigner};
+
+pub mod apportionment;
+pub mod block0;
+pub mod cbor;
+pub mod cip36;
+pub mod db_sync;
+pub mod diff;
+pub mod explain;
 mod influence_cap;
+pub mod merkle;
+pub mod metrics;
 pub mod registration;
+pub mod reward_address;
+pub mod stream;
+pub mod transform;
 mod voter_hir;
 pub mod voting_group;
 
-pub const CATALYST_VOTING_PURPOSE_TAG: u64 = 0;
+pub const CATALYST_VOTING_PURPOSE_TAG: VotingPurpose = 0;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct RawSnapshot(Vec<VotingRegistration>);
 
 impl From<Vec<VotingRegistration>> for RawSnapshot {
@@ -27,8 +47,10 @@ impl From<Vec<VotingRegistration>> for RawSnapshot {
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("insufficient number of voters to guarantee voting influence cap")]
-    NotEnoughVoters,
+    #[error(
+        "insufficient number of voters in voting group '{0}' to guarantee voting influence cap"
+    )]
+    NotEnoughVoters(VotingGroup),
     #[error("voting power overflow")]
     Overflow,
 }
@@ -39,6 +61,15 @@ pub struct KeyContribution {
     pub stake_public_key: MainnetStakeAddress,
     pub reward_address: MainnetRewardAddress,
     pub value: u64,
+    /// Weight of the delegation to the voting key in a CIP-36 registration, not present for
+    /// legacy registrations. Repeated delegations to the same key in a registration result in
+    /// a single contribution, whose value and weight are the sum of those of the delegations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u64>,
+    /// Voting power effectively contributed by this stake key after transformations and the
+    /// voting power cap, i.e. its share of the voting power in the VoterHIR
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effective_value: Option<u64>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -46,110 +77,303 @@ pub struct SnapshotInfo {
     /// The values in the contributions are the original values in the registration transactions and
     /// thus retain the original proportions.
     /// However, it's possible that the sum of those values is greater than the voting power assigned in the
-    /// VoterHIR, due to voting power caps or additional transformations.
+    /// VoterHIR, due to voting power caps or additional transformations. The voting power actually
+    /// contributed by each stake key is found in `effective_value`.
     pub contributions: Vec<KeyContribution>,
     pub hir: VoterHIR,
+    /// Voting power of this key before any transformation (e.g. the voting power cap) was applied.
+    /// The voting power after transformations is the one in the VoterHIR.
+    #[serde(default)]
+    pub raw_voting_power: Value,
+    /// Voting power of this key after transformations, right before the voting power cap
+    #[serde(default)]
+    pub pre_cap_voting_power: Value,
+    /// Voting power removed from this key by the voting power cap
+    #[serde(default)]
+    pub cap_reduction: Value,
+}
+
+impl SnapshotInfo {
+    /// Returns false for snapshots produced before the voting power before the cap was
+    /// recorded, for which `pre_cap_voting_power` and `cap_reduction` default to 0.
+    /// A key with voting power always has a non zero voting power before the cap.
+    pub fn has_cap_info(&self) -> bool {
+        self.pre_cap_voting_power > 0.into() || self.hir.voting_power == 0.into()
+    }
+
+    /// Record the reduction applied by the voting power cap and split the final voting power
+    /// among contributions, in proportion to their original values
+    fn record_cap(&mut self) {
+        let voting_power = u64::from(self.hir.voting_power);
+        self.cap_reduction = u64::from(self.pre_cap_voting_power)
+            .saturating_sub(voting_power)
+            .into();
+        let values = self
+            .contributions
+            .iter()
+            .map(|c| c.value)
+            .collect::<Vec<_>>();
+        for (contribution, effective_value) in self
+            .contributions
+            .iter_mut()
+            .zip(apportionment::largest_remainder(voting_power, &values))
+        {
+            contribution.effective_value = Some(effective_value);
+        }
+    }
 }
 
+/// Additional parameters controlling how a [`RawSnapshot`] is processed.
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct Snapshot {
-    // a raw public key is preferred so that we don't have to worry about discrimination when deserializing from
-    // a CIP-36 compatible encoding
-    inner: BTreeMap<Identifier, SnapshotInfo>,
+pub struct SnapshotOptions {
+    /// Only registrations tagged with one of these voting purposes are
+    /// considered for the snapshot.
+    pub voting_purposes: BTreeSet<VotingPurpose>,
+    /// Registrations included in a block after this slot are discarded.
+    /// Registrations with no slot information are always retained.
+    pub registration_deadline_slot: Option<u64>,
+    /// Transformations applied in order to the voting power of each key,
+    /// before the voting power cap.
+    pub transforms: Vec<Transform>,
+    /// Voting power cap for specific voting groups, overriding the default one.
+    /// The cap is always applied among voters of the same voting group.
+    pub group_caps: BTreeMap<VotingGroup, Fraction>,
+    /// Minimum voting power for a voting key to be included in the snapshot, for specific
+    /// voting groups. Unlike the stake threshold, which applies to each registration, this
+    /// is applied after voting group assignment to the total voting power of each key.
+    pub group_thresholds: BTreeMap<VotingGroup, Value>,
+    /// If set, registrations whose reward address is malformed or for a different
+    /// network are discarded.
+    pub network: Option<Network>,
+    /// How the voting power of CIP-36 registrations is split among their delegations
+    pub apportionment: Apportionment,
+}
+
+impl SnapshotOptions {
+    pub fn for_purposes(voting_purposes: impl IntoIterator<Item = VotingPurpose>) -> Self {
+        Self {
+            voting_purposes: voting_purposes.into_iter().collect(),
+            registration_deadline_slot: None,
+            transforms: Vec::new(),
+            group_caps: BTreeMap::new(),
+            group_thresholds: BTreeMap::new(),
+            network: None,
+            apportionment: Apportionment::default(),
+        }
+    }
+}
+
+impl Default for SnapshotOptions {
+    fn default() -> Self {
+        Self::for_purposes([CATALYST_VOTING_PURPOSE_TAG])
+    }
+}
+
+/// Registrations which were not considered when processing a [`RawSnapshot`], or whose
+/// reward address cannot be paid
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotReport {
+    /// Registrations replaced by a registration with a higher nonce for the
+    /// same stake key and voting purpose. Registrations without a nonce are
+    /// replaced by any registration with a nonce.
+    pub superseded: Vec<VotingRegistration>,
+    /// Registrations included in a block after the registration deadline
+    pub after_deadline: Vec<VotingRegistration>,
+    /// Registrations whose reward address is not for the expected network
+    pub wrong_network: Vec<VotingRegistration>,
+    /// Registrations whose reward address is not a valid reward address. They are reported
+    /// whether or not a network is expected, but only discarded if it is.
+    pub malformed_reward_address: Vec<VotingRegistration>,
+    /// Voting keys excluded because their voting power is below the threshold of their voting group
+    pub below_group_threshold: Vec<ExcludedVoter>,
+}
+
+impl SnapshotReport {
+    /// Add the registrations listed in `entries`, as reported by a [`SnapshotBuilder`]
+    /// which processed `registrations`, in the order of the input.
+    ///
+    /// Panics if an entry does not refer to one of `registrations`.
+    pub fn add_entries(
+        &mut self,
+        mut entries: Vec<ReportEntry>,
+        registrations: &[VotingRegistration],
+    ) {
+        entries.sort_by_key(|entry| entry.index);
+        for entry in entries {
+            let registration = registrations[entry.index].clone();
+            match entry.reason {
+                ReportReason::Superseded => self.superseded.push(registration),
+                ReportReason::AfterDeadline => self.after_deadline.push(registration),
+                ReportReason::WrongNetwork => self.wrong_network.push(registration),
+                ReportReason::MalformedRewardAddress => {
+                    self.malformed_reward_address.push(registration)
+                }
+            }
+        }
+    }
+}
+
+/// A voting key excluded from the snapshot after voting group assignment
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExcludedVoter {
+    #[serde(flatten)]
+    pub hir: VoterHIR,
+    /// Minimum voting power required for the voting group
+    pub threshold: Value,
+}
+
+/// Voters of a snapshot before the voting power cap is applied, from which snapshots with
+/// different caps can be derived without processing registrations again
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UncappedSnapshot {
+    voters: Vec<SnapshotInfo>,
     stake_threshold: Value,
+    group_caps: BTreeMap<VotingGroup, Fraction>,
 }
 
-impl Snapshot {
+impl UncappedSnapshot {
+    /// Same as [`Snapshot::from_raw_snapshot_with_options`], without the voting power cap
     pub fn from_raw_snapshot(
-        raw_snapshot: RawSnapshot,
+        raw_snapshot: &RawSnapshot,
         stake_threshold: Value,
-        cap: Fraction,
         voting_group_assigner: &impl VotingGroupAssigner,
-    ) -> Result<Self, Error> {
-        let raw_contribs = raw_snapshot
-            .0
+        options: &SnapshotOptions,
+    ) -> Result<(Self, SnapshotReport), Error> {
+        let (sink, entries) = stream::collecting_report();
+        let mut builder = SnapshotBuilder::new(stake_threshold, options.clone()).with_report(sink);
+        for registration in &raw_snapshot.0 {
+            builder.push(registration.clone());
+        }
+        let (uncapped, mut report) = builder.build_uncapped(voting_group_assigner)?;
+        let entries = std::mem::take(&mut *entries.lock().expect("lock poisoned"));
+        report.add_entries(entries, &raw_snapshot.0);
+        Ok((uncapped, report))
+    }
+
+    /// Assign voting groups and apply thresholds and transformations to the contributions
+    /// to each voting key
+    pub(crate) fn from_contributions(
+        raw_contribs: BTreeMap<Identifier, Vec<KeyContribution>>,
+        delegation_kinds: &BTreeMap<Identifier, DelegationKinds>,
+        stake_threshold: Value,
+        voting_group_assigner: &impl VotingGroupAssigner,
+        options: &SnapshotOptions,
+        mut report: SnapshotReport,
+    ) -> Result<(Self, SnapshotReport), Error> {
+        let entries = raw_contribs
             .into_iter()
-            // Discard registrations with 0 voting power since they don't influence
-            // snapshot anyway
-            .filter(|reg| reg.voting_power >= std::cmp::max(stake_threshold, 1.into()))
-            // TODO: add capability to select voting purpose for a snapshot.
-            // At the moment Catalyst is the only one in use
-            .filter(|reg| reg.voting_purpose == CATALYST_VOTING_PURPOSE_TAG)
-            .fold(BTreeMap::new(), |mut acc: BTreeMap<_, Vec<_>>, reg| {
-                let VotingRegistration {
-                    reward_address,
-                    delegations,
+            .map(|(k, contributions)| {
+                let voting_power = contributions.iter().map(|c| c.value).sum::<u64>().into();
+                let voting_group = voting_group_assigner.assign_voter(&VoterInfo {
+                    voting_key: &k,
                     voting_power,
-                    stake_public_key,
-                    ..
-                } = reg;
-
-                match delegations {
-                    Delegations::Legacy(vk) => {
-                        acc.entry(vk).or_default().push(KeyContribution {
-                            stake_public_key,
-                            reward_address,
-                            value: voting_power.into(),
-                        });
-                    }
-                    Delegations::New(mut vks) => {
-                        let voting_power = u64::from(voting_power);
-                        let total_weights =
-                            NonZeroU64::new(vks.iter().map(|(_, weight)| *weight as u64).sum());
-
-                        let last = vks.pop().expect("CIP36 requires at least 1 delegation");
-                        let others_total_vp = total_weights.map_or(0, |non_zero_total| {
-                            vks.into_iter()
-                                .filter_map(|(vk, weight)| {
-                                    NonZeroU64::new((voting_power * weight as u64) / non_zero_total)
-                                        .map(|value| (vk, value))
-                                })
-                                .map(|(vk, value)| {
-                                    acc.entry(vk).or_default().push(KeyContribution {
-                                        stake_public_key: stake_public_key.clone(),
-                                        reward_address: reward_address.clone(),
-                                        value: value.get(),
-                                    });
-                                    value.get()
-                                })
-                                .sum::<u64>()
-                        });
-                        acc.entry(last.0).or_default().push(KeyContribution {
-                            stake_public_key,
-                            reward_address,
-                            value: voting_power - others_total_vp,
+                    delegation_kinds: delegation_kinds.get(&k).copied().unwrap_or_default(),
+                });
+                SnapshotInfo {
+                    hir: VoterHIR {
+                        voting_group,
+                        voting_key: k,
+                        voting_power,
+                    },
+                    contributions,
+                    raw_voting_power: voting_power,
+                    pre_cap_voting_power: voting_power,
+                    cap_reduction: 0.into(),
+                }
+            })
+            .filter(
+                |entry| match options.group_thresholds.get(&entry.hir.voting_group) {
+                    Some(threshold) if entry.hir.voting_power < *threshold => {
+                        report.below_group_threshold.push(ExcludedVoter {
+                            hir: entry.hir.clone(),
+                            threshold: *threshold,
                         });
+                        false
                     }
-                };
-                acc
-            });
-        let entries = raw_contribs
-            .into_iter()
-            .map(|(k, contributions)| SnapshotInfo {
-                hir: VoterHIR {
-                    voting_group: voting_group_assigner.assign(&k),
-                    voting_key: k,
-                    voting_power: contributions.iter().map(|c| c.value).sum::<u64>().into(),
+                    _ => true,
                 },
-                contributions,
-            })
+            )
             .collect();
-        Ok(Self {
-            inner: Self::apply_voting_power_cap(entries, cap)?
+        let entries = options.transforms.transform(entries)?;
+        let uncapped = Self {
+            voters: entries,
+            stake_threshold,
+            group_caps: options.group_caps.clone(),
+        };
+        Ok((uncapped, report))
+    }
+
+    /// Apply the voting power cap, with the caps of specific voting groups taken
+    /// from the options the snapshot was built with
+    pub fn cap(&self, cap: Fraction) -> Result<Snapshot, Error> {
+        Ok(Snapshot {
+            inner: Snapshot::apply_voting_power_cap(self.voters.clone(), cap, &self.group_caps)?
                 .into_iter()
                 .map(|entry| (entry.hir.voting_key.clone(), entry))
                 .collect(),
-            stake_threshold,
+            stake_threshold: self.stake_threshold,
         })
     }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    // a raw public key is preferred so that we don't have to worry about discrimination when deserializing from
+    // a CIP-36 compatible encoding
+    inner: BTreeMap<Identifier, SnapshotInfo>,
+    stake_threshold: Value,
+}
+
+impl Snapshot {
+    pub fn from_raw_snapshot(
+        raw_snapshot: RawSnapshot,
+        stake_threshold: Value,
+        cap: Fraction,
+        voting_group_assigner: &impl VotingGroupAssigner,
+    ) -> Result<Self, Error> {
+        Self::from_raw_snapshot_with_options(
+            raw_snapshot,
+            stake_threshold,
+            cap,
+            voting_group_assigner,
+            &SnapshotOptions::default(),
+        )
+        .map(|(snapshot, _report)| snapshot)
+    }
+
+    /// Process a [`RawSnapshot`], also returning a report of the registrations
+    /// that were superseded or discarded according to CIP-36 rules.
+    pub fn from_raw_snapshot_with_options(
+        raw_snapshot: RawSnapshot,
+        stake_threshold: Value,
+        cap: Fraction,
+        voting_group_assigner: &impl VotingGroupAssigner,
+        options: &SnapshotOptions,
+    ) -> Result<(Self, SnapshotReport), Error> {
+        let (uncapped, report) = UncappedSnapshot::from_raw_snapshot(
+            &raw_snapshot,
+            stake_threshold,
+            voting_group_assigner,
+            options,
+        )?;
+        Ok((uncapped.cap(cap)?, report))
+    }
 
     fn apply_voting_power_cap(
         voters: Vec<SnapshotInfo>,
         cap: Fraction,
+        group_caps: &BTreeMap<VotingGroup, Fraction>,
     ) -> Result<Vec<SnapshotInfo>, Error> {
-        Ok(influence_cap::cap_voting_influence(voters, cap)?
+        let voters = voters
             .into_iter()
-            .collect())
+            .map(|mut voter| {
+                voter.pre_cap_voting_power = voter.hir.voting_power;
+                voter
+            })
+            .collect();
+        let mut voters = influence_cap::cap_voting_influence_per_group(voters, cap, group_caps)?;
+        voters.iter_mut().for_each(SnapshotInfo::record_cap);
+        Ok(voters)
     }
 
     pub fn stake_threshold(&self) -> Value {
@@ -186,8 +410,8 @@ impl Snapshot {
 #[cfg(any(test, feature = "proptest"))]
 pub mod tests {
     use super::*;
-    use chain_addr::{Discrimination, Kind};
-    use jormungandr_lib::interfaces::{Address, InitialUTxO};
+    #[cfg(test)]
+    use crate::registration::Delegations;
     use proptest::prelude::*;
     #[cfg(test)]
     use test_strategy::proptest;
@@ -195,26 +419,11 @@ pub mod tests {
     struct DummyAssigner;
 
     impl VotingGroupAssigner for DummyAssigner {
-        fn assign(&self, _vk: &Identifier) -> String {
+        fn assign_voter(&self, _voter: &VoterInfo) -> String {
             String::new()
         }
     }
 
-    impl Snapshot {
-        pub fn to_block0_initials(&self, discrimination: Discrimination) -> Vec<InitialUTxO> {
-            self.inner
-                .iter()
-                .map(|(vk, entry)| {
-                    let value = entry.hir.voting_power;
-                    let address: Address =
-                        chain_addr::Address(discrimination, Kind::Account(vk.to_inner().into()))
-                            .into();
-                    InitialUTxO { address, value }
-                })
-                .collect::<Vec<_>>()
-        }
-    }
-
     impl Arbitrary for RawSnapshot {
         type Parameters = ();
         type Strategy = BoxedStrategy<RawSnapshot>;
@@ -271,19 +480,25 @@ pub mod tests {
     #[cfg(test)]
     #[proptest]
     fn test_voting_power_all_distributed(reg: VotingRegistration) {
-        let snapshot = Snapshot::from_raw_snapshot(
-            vec![reg.clone()].into(),
-            0.into(),
-            Fraction::from(1),
-            &|_vk: &Identifier| String::new(),
-        )
-        .unwrap();
-        let total_stake = snapshot
-            .to_voter_hir()
-            .into_iter()
-            .map(|hir| u64::from(hir.voting_power))
-            .sum::<u64>();
-        assert_eq!(total_stake, u64::from(reg.voting_power))
+        for apportionment in [Apportionment::LastKey, Apportionment::LargestRemainder] {
+            let (snapshot, _) = Snapshot::from_raw_snapshot_with_options(
+                vec![reg.clone()].into(),
+                0.into(),
+                Fraction::from(1),
+                &|_vk: &Identifier| String::new(),
+                &SnapshotOptions {
+                    apportionment,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let total_stake = snapshot
+                .to_voter_hir()
+                .into_iter()
+                .map(|hir| u64::from(hir.voting_power))
+                .sum::<u64>();
+            assert_eq!(total_stake, u64::from(reg.voting_power))
+        }
     }
 
     #[cfg(test)]
@@ -308,6 +523,357 @@ pub mod tests {
         )
     }
 
+    #[cfg(test)]
+    #[proptest]
+    fn test_selected_purpose_is_retained(mut reg: VotingRegistration, purpose: VotingPurpose) {
+        let catalyst = Snapshot::from_raw_snapshot(
+            vec![reg.clone()].into(),
+            0.into(),
+            Fraction::from(1u64),
+            &DummyAssigner,
+        )
+        .unwrap();
+        reg.voting_purpose = purpose;
+        assert_eq!(
+            Snapshot::from_raw_snapshot_with_options(
+                vec![reg.clone()].into(),
+                0.into(),
+                Fraction::from(1u64),
+                &DummyAssigner,
+                &SnapshotOptions::for_purposes([purpose]),
+            )
+            .unwrap()
+            .0,
+            catalyst
+        );
+        assert_eq!(
+            Snapshot::from_raw_snapshot_with_options(
+                vec![reg].into(),
+                0.into(),
+                Fraction::from(1u64),
+                &DummyAssigner,
+                &SnapshotOptions::for_purposes([CATALYST_VOTING_PURPOSE_TAG, purpose]),
+            )
+            .unwrap()
+            .0,
+            catalyst
+        );
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn test_latest_nonce_wins() {
+        let vk_1 = Identifier::from_hex(&hex::encode([0; 32])).unwrap();
+        let vk_2 = Identifier::from_hex(&hex::encode([1; 32])).unwrap();
+        let reg = |vk: &Identifier, nonce, slot| VotingRegistration {
+            stake_public_key: "stake_key".to_string(),
+            voting_power: 100.into(),
+            reward_address: String::new(),
+            delegations: Delegations::Legacy(vk.clone()),
+            voting_purpose: 0,
+            nonce: Some(nonce),
+            slot: Some(slot),
+        };
+        let old = reg(&vk_1, 1, 10);
+        let new = reg(&vk_2, 2, 5);
+
+        let (snapshot, report) = Snapshot::from_raw_snapshot_with_options(
+            vec![new, old.clone()].into(),
+            0.into(),
+            Fraction::from(1u64),
+            &DummyAssigner,
+            &SnapshotOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(snapshot.voting_keys().collect::<Vec<_>>(), vec![&vk_2]);
+        assert_eq!(report.superseded, vec![old]);
+        assert!(report.after_deadline.is_empty());
+
+        // the latest registration is submitted too late, the previous one is valid
+        let (snapshot, report) = Snapshot::from_raw_snapshot_with_options(
+            vec![reg(&vk_1, 1, 10), reg(&vk_2, 2, 20)].into(),
+            0.into(),
+            Fraction::from(1u64),
+            &DummyAssigner,
+            &SnapshotOptions {
+                registration_deadline_slot: Some(15),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(snapshot.voting_keys().collect::<Vec<_>>(), vec![&vk_1]);
+        assert!(report.superseded.is_empty());
+        assert_eq!(report.after_deadline, vec![reg(&vk_2, 2, 20)]);
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn test_group_thresholds() {
+        let rep = Identifier::from_hex(&hex::encode([0; 32])).unwrap();
+        let direct = Identifier::from_hex(&hex::encode([1; 32])).unwrap();
+        let reg = |vk: &Identifier, voting_power: u64| VotingRegistration {
+            stake_public_key: String::new(),
+            voting_power: voting_power.into(),
+            reward_address: String::new(),
+            delegations: Delegations::Legacy(vk.clone()),
+            voting_purpose: 0,
+            nonce: None,
+            slot: None,
+        };
+        let assigner = |vk: &Identifier| {
+            if vk == &rep {
+                "rep".to_string()
+            } else {
+                "direct".to_string()
+            }
+        };
+        let raw: RawSnapshot = vec![reg(&rep, 60), reg(&rep, 60), reg(&direct, 100)].into();
+        let options = |threshold: u64| SnapshotOptions {
+            group_thresholds: [("rep".to_string(), threshold.into())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+
+        // the threshold applies to the total voting power of the key
+        let (snapshot, report) = Snapshot::from_raw_snapshot_with_options(
+            raw.clone(),
+            0.into(),
+            Fraction::from(1u64),
+            &assigner,
+            &options(120),
+        )
+        .unwrap();
+        assert_eq!(snapshot.voting_keys().count(), 2);
+        assert!(report.below_group_threshold.is_empty());
+
+        let (snapshot, report) = Snapshot::from_raw_snapshot_with_options(
+            raw,
+            0.into(),
+            Fraction::from(1u64),
+            &assigner,
+            &options(121),
+        )
+        .unwrap();
+        assert_eq!(snapshot.voting_keys().collect::<Vec<_>>(), vec![&direct]);
+        assert_eq!(
+            report.below_group_threshold,
+            vec![ExcludedVoter {
+                hir: VoterHIR {
+                    voting_key: rep,
+                    voting_group: "rep".to_string(),
+                    voting_power: 120.into(),
+                },
+                threshold: 121.into(),
+            }]
+        );
+    }
+
+    #[cfg(test)]
+    #[proptest]
+    fn test_registrations_without_nonce_are_retained(raw: RawSnapshot, deadline: u64) {
+        let (_, report) = Snapshot::from_raw_snapshot_with_options(
+            raw,
+            0.into(),
+            Fraction::from(1u64),
+            &DummyAssigner,
+            &SnapshotOptions {
+                registration_deadline_slot: Some(deadline),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(report, SnapshotReport::default());
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn test_nonce_supersedes_registrations_without_nonce() {
+        let vk = Identifier::from_hex(&hex::encode([0; 32])).unwrap();
+        let reg = |stake_key: &str, nonce| VotingRegistration {
+            stake_public_key: stake_key.to_string(),
+            voting_power: 100.into(),
+            reward_address: String::new(),
+            delegations: Delegations::Legacy(vk.clone()),
+            voting_purpose: 0,
+            nonce,
+            slot: None,
+        };
+        let without_nonce = reg("stake_key", None);
+        let raw: RawSnapshot = vec![
+            without_nonce.clone(),
+            reg("stake_key", Some(1)),
+            // no registration with a nonce for this stake key, both are retained
+            reg("other_stake_key", None),
+            reg("other_stake_key", None),
+        ]
+        .into();
+
+        let (snapshot, report) = Snapshot::from_raw_snapshot_with_options(
+            raw,
+            0.into(),
+            Fraction::from(1u64),
+            &DummyAssigner,
+            &SnapshotOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(report.superseded, vec![without_nonce]);
+        let stake_keys = snapshot
+            .contributions_for_voting_key(vk)
+            .into_iter()
+            .map(|c| (c.stake_public_key, c.value))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stake_keys,
+            vec![
+                ("stake_key".to_string(), 100),
+                ("other_stake_key".to_string(), 100),
+                ("other_stake_key".to_string(), 100),
+            ]
+        );
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn test_repeated_delegations_are_merged() {
+        let vk_1 = Identifier::from_hex(&hex::encode([0; 32])).unwrap();
+        let vk_2 = Identifier::from_hex(&hex::encode([1; 32])).unwrap();
+        let raw: RawSnapshot = vec![VotingRegistration {
+            stake_public_key: String::new(),
+            voting_power: 100.into(),
+            reward_address: String::new(),
+            delegations: Delegations::New(vec![
+                (vk_1.clone(), 1),
+                (vk_2.clone(), 1),
+                (vk_1.clone(), 2),
+            ]),
+            voting_purpose: 0,
+            nonce: None,
+            slot: None,
+        }]
+        .into();
+
+        for apportionment in [Apportionment::LastKey, Apportionment::LargestRemainder] {
+            let (snapshot, _) = Snapshot::from_raw_snapshot_with_options(
+                raw.clone(),
+                0.into(),
+                Fraction::from(1u64),
+                &DummyAssigner,
+                &SnapshotOptions {
+                    apportionment,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let contributions = |vk: &Identifier| {
+                snapshot
+                    .contributions_for_voting_key(vk)
+                    .into_iter()
+                    .map(|c| (c.value, c.weight))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(contributions(&vk_1), vec![(75, Some(3))]);
+            assert_eq!(contributions(&vk_2), vec![(25, Some(1))]);
+        }
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn test_rule_based_assigner() {
+        use crate::voting_group::RuleBasedAssigner;
+
+        let legacy = Identifier::from_hex(&hex::encode([0; 32])).unwrap();
+        let cip36 = Identifier::from_hex(&hex::encode([1; 32])).unwrap();
+        let reg = |delegations, voting_power: u64| VotingRegistration {
+            stake_public_key: String::new(),
+            voting_power: voting_power.into(),
+            reward_address: String::new(),
+            delegations,
+            voting_purpose: 0,
+            nonce: None,
+            slot: None,
+        };
+        let raw: RawSnapshot = vec![
+            reg(Delegations::Legacy(legacy.clone()), 100),
+            reg(Delegations::New(vec![(cip36.clone(), 1)]), 60),
+            reg(Delegations::New(vec![(cip36.clone(), 1)]), 60),
+        ]
+        .into();
+        let assigner: RuleBasedAssigner = serde_json::from_str(
+            r#"{
+                "rules": [
+                    {"group": "legacy", "registration": "legacy"},
+                    {"group": "large", "min_voting_power": 120}
+                ],
+                "default": "small"
+            }"#,
+        )
+        .unwrap();
+
+        let snapshot =
+            Snapshot::from_raw_snapshot(raw, 0.into(), Fraction::from(1u64), &assigner).unwrap();
+        let groups = snapshot
+            .to_voter_hir()
+            .into_iter()
+            .map(|hir| (hir.voting_key, hir.voting_group))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(groups[&legacy], "legacy");
+        // the rule is applied to the total voting power of the key
+        assert_eq!(groups[&cip36], "large");
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn test_wrong_network_is_reported() {
+        let vk = Identifier::from_hex(&hex::encode([0; 32])).unwrap();
+        let reg = |reward_address: String| VotingRegistration {
+            stake_public_key: reward_address.clone(),
+            voting_power: 100.into(),
+            reward_address,
+            delegations: Delegations::Legacy(vk.clone()),
+            voting_purpose: 0,
+            nonce: None,
+            slot: None,
+        };
+        let mainnet = reg(format!("0xe1{}", hex::encode([1; 28])));
+        let testnet = reg(format!("0xe0{}", hex::encode([2; 28])));
+        let malformed = reg("0x01".to_string());
+        let base = reg(format!("0x01{}", hex::encode([3; 56])));
+        let raw: RawSnapshot =
+            vec![mainnet, testnet.clone(), malformed.clone(), base.clone()].into();
+
+        let (snapshot, report) = Snapshot::from_raw_snapshot_with_options(
+            raw.clone(),
+            0.into(),
+            Fraction::from(1u64),
+            &DummyAssigner,
+            &SnapshotOptions {
+                network: Some(Network::Mainnet),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(snapshot.contributions_for_voting_key(vk.clone()).len(), 1);
+        assert_eq!(report.wrong_network, vec![testnet]);
+        assert_eq!(
+            report.malformed_reward_address,
+            vec![malformed.clone(), base.clone()]
+        );
+
+        // malformed addresses are still reported if the network is not specified
+        let (snapshot, report) = Snapshot::from_raw_snapshot_with_options(
+            raw,
+            0.into(),
+            Fraction::from(1u64),
+            &DummyAssigner,
+            &SnapshotOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(snapshot.contributions_for_voting_key(vk).len(), 4);
+        assert!(report.wrong_network.is_empty());
+        assert_eq!(report.malformed_reward_address, vec![malformed, base]);
+    }
+
     #[cfg(test)]
     #[test]
     fn test_distribution() {
@@ -327,6 +893,8 @@ pub mod tests {
                 reward_address: String::new(),
                 delegations,
                 voting_purpose: 0,
+                nonce: None,
+                slot: None,
             });
         }
 
@@ -390,5 +958,15 @@ pub mod tests {
             .value,
             44422342528
         );
+        assert_eq!(
+            snapshot.contributions_for_voting_key(
+                Identifier::from_hex(
+                    "a6a3c0447aeb9cc54cf6422ba32b294e5e1c3ef6d782f2acff4a70694c4d1663"
+                )
+                .unwrap()
+            )[0]
+            .weight,
+            Some(3)
+        );
     }
 }
@@ -0,0 +1,284 @@
+//! Merkle commitment to the voters in a snapshot.
+//!
+//! Leaves are the canonical encoding of each [`VoterHIR`], sorted by voting key:
+//!
+//! | field        | encoding                                  |
+//! |--------------|-------------------------------------------|
+//! | voting key   | 32 bytes                                  |
+//! | voting group | length as big endian u32, then utf-8 bytes |
+//! | voting power | big endian u64                            |
+//!
+//! Leaf hashes are `blake2b256(0x00 | leaf)` and inner nodes are `blake2b256(0x01 | left | right)`.
+//! When a level has an odd number of nodes, the last one is promoted unchanged to the next level.
+//! The root of an empty tree is `blake2b256("")`.
+//!
+//! Both the Merkle root and the canonical encoding of a [`Snapshot`] commit to the final voting
+//! power of each voter only. The parameters used to compute it (voting power cap, transformations,
+//! voting group rules, ...) are not part of the snapshot and are out of scope of the commitment,
+//! they must be published separately for the snapshot to be reproduced. The stake threshold, which
+//! is kept in the snapshot, is included in the canonical encoding.
+use crate::{Snapshot, VoterHIR};
+use chain_crypto::hash::Blake2b256;
+use jormungandr_lib::crypto::account::Identifier;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+const LEAF_TAG: u8 = 0;
+const NODE_TAG: u8 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Hash([u8; 32]);
+
+impl Hash {
+    fn digest(bytes: &[u8]) -> Self {
+        let mut hash = [0; 32];
+        hash.copy_from_slice(Blake2b256::new(bytes).as_ref());
+        Self(hash)
+    }
+
+    fn leaf(voter: &VoterHIR) -> Self {
+        let mut bytes = vec![LEAF_TAG];
+        encode_voter(voter, &mut bytes);
+        Self::digest(&bytes)
+    }
+
+    fn node(left: &Hash, right: &Hash) -> Self {
+        let mut bytes = Vec::with_capacity(1 + 2 * 32);
+        bytes.push(NODE_TAG);
+        bytes.extend_from_slice(&left.0);
+        bytes.extend_from_slice(&right.0);
+        Self::digest(&bytes)
+    }
+}
+
+impl AsRef<[u8]> for Hash {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl FromStr for Hash {
+    type Err = hex::FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut hash = [0; 32];
+        hex::decode_to_slice(s.trim_start_matches("0x"), &mut hash)?;
+        Ok(Self(hash))
+    }
+}
+
+impl Serialize for Hash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(|e| D::Error::custom(format!("invalid hash: {}", e)))
+    }
+}
+
+fn encode_voter(voter: &VoterHIR, bytes: &mut Vec<u8>) {
+    let group = voter.voting_group.as_bytes();
+    bytes.extend_from_slice(voter.voting_key.as_ref().as_ref());
+    bytes.extend_from_slice(&(group.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(group);
+    bytes.extend_from_slice(&u64::from(voter.voting_power).to_be_bytes());
+}
+
+/// Position of a sibling node with respect to the node being proved
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofStep {
+    pub side: Side,
+    pub hash: Hash,
+}
+
+/// Proof that a [`VoterHIR`] is included in a snapshot with a given Merkle root
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InclusionProof {
+    pub voter: VoterHIR,
+    /// Sibling hashes from the leaf up to the root
+    pub path: Vec<ProofStep>,
+}
+
+impl InclusionProof {
+    pub fn root(&self) -> Hash {
+        self.path
+            .iter()
+            .fold(Hash::leaf(&self.voter), |hash, step| match step.side {
+                Side::Left => Hash::node(&step.hash, &hash),
+                Side::Right => Hash::node(&hash, &step.hash),
+            })
+    }
+
+    pub fn verify(&self, root: &Hash) -> bool {
+        &self.root() == root
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MerkleTree {
+    voters: Vec<VoterHIR>,
+    /// Hashes of each level of the tree, starting from the leaves
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    /// Build the tree over the given voters. Voters are sorted by voting key,
+    /// so the order in input does not influence the result.
+    pub fn new(mut voters: Vec<VoterHIR>) -> Self {
+        voters.sort_by(|a, b| {
+            a.voting_key
+                .cmp(&b.voting_key)
+                .then_with(|| a.voting_group.cmp(&b.voting_group))
+        });
+        let mut levels = vec![voters.iter().map(Hash::leaf).collect::<Vec<_>>()];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => Hash::node(left, right),
+                    [last] => *last,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Self { voters, levels }
+    }
+
+    pub fn root(&self) -> Hash {
+        self.levels
+            .last()
+            .and_then(|level| level.first())
+            .copied()
+            .unwrap_or_else(|| Hash::digest(&[]))
+    }
+
+    /// Inclusion proofs for all entries of a voting key, one for each voting group
+    /// the key belongs to
+    pub fn proofs(&self, voting_key: &Identifier) -> Vec<InclusionProof> {
+        self.voters
+            .iter()
+            .enumerate()
+            .filter(|(_, voter)| &voter.voting_key == voting_key)
+            .map(|(index, _)| self.proof_at(index))
+            .collect()
+    }
+
+    /// Inclusion proofs for all entries in the tree
+    pub fn all_proofs(&self) -> Vec<InclusionProof> {
+        (0..self.voters.len())
+            .map(|index| self.proof_at(index))
+            .collect()
+    }
+
+    fn proof_at(&self, mut index: usize) -> InclusionProof {
+        let voter = self.voters[index].clone();
+        let mut path = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if let Some(hash) = level.get(sibling) {
+                let side = if sibling < index {
+                    Side::Left
+                } else {
+                    Side::Right
+                };
+                path.push(ProofStep { side, hash: *hash });
+            }
+            index /= 2;
+        }
+        InclusionProof { voter, path }
+    }
+}
+
+impl Snapshot {
+    /// Canonical binary encoding of the snapshot: the stake threshold and the number of voters
+    /// as big endian u64s, followed by the encoding of each voter, sorted by voting key.
+    /// See the [module](crate::merkle) documentation for details.
+    pub fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut bytes = u64::from(self.stake_threshold).to_be_bytes().to_vec();
+        bytes.extend_from_slice(&(self.inner.len() as u64).to_be_bytes());
+        for entry in self.inner.values() {
+            encode_voter(&entry.hir, &mut bytes);
+        }
+        bytes
+    }
+
+    pub fn merkle_tree(&self) -> MerkleTree {
+        MerkleTree::new(self.to_voter_hir())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_strategy::proptest;
+
+    #[proptest]
+    fn test_all_proofs_verify(snapshot: Snapshot) {
+        let tree = snapshot.merkle_tree();
+        let root = tree.root();
+        for voting_key in snapshot.voting_keys() {
+            let proofs = tree.proofs(voting_key);
+            assert_eq!(proofs.len(), 1);
+            assert!(proofs[0].verify(&root));
+        }
+    }
+
+    #[proptest]
+    fn test_order_does_not_matter(snapshot: Snapshot) {
+        let mut voters = snapshot.to_voter_hir();
+        voters.reverse();
+        assert_eq!(
+            MerkleTree::new(voters).root(),
+            snapshot.merkle_tree().root()
+        );
+    }
+
+    #[proptest]
+    fn test_tampered_proof_fails(snapshot: Snapshot) {
+        let tree = snapshot.merkle_tree();
+        let root = tree.root();
+        for mut proof in tree.all_proofs() {
+            proof.voter.voting_power = (u64::from(proof.voter.voting_power) + 1).into();
+            assert!(!proof.verify(&root));
+        }
+    }
+
+    #[proptest]
+    fn test_canonical_bytes_include_stake_threshold(snapshot: Snapshot) {
+        let mut other = snapshot.clone();
+        other.stake_threshold = (u64::from(snapshot.stake_threshold) ^ 1).into();
+        assert_ne!(snapshot.to_canonical_bytes(), other.to_canonical_bytes());
+        assert_eq!(
+            snapshot.to_canonical_bytes()[..8],
+            u64::from(snapshot.stake_threshold).to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn test_hash_serde() {
+        let hash = Hash::digest(b"catalyst");
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(serde_json::from_str::<Hash>(&json).unwrap(), hash);
+    }
+}
@@ -0,0 +1,264 @@
+//! Decentralization metrics of the voting power distribution in a snapshot.
+//!
+//! Metrics are computed on the voting power of each key right before the voting power cap
+//! (`pre_cap_voting_power`) and after it (`hir.voting_power`), both for the whole snapshot
+//! and for each voting group.
+use crate::{Snapshot, SnapshotInfo, VotingGroup};
+use jormungandr_lib::interfaces::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(
+        "voting power before the cap is missing for voting key {0}, the snapshot was produced by an older version of the toolbox"
+    )]
+    MissingCapInfo(String),
+}
+
+/// Sizes of the top-N shares reported when none are specified
+pub const DEFAULT_TOP_N: [usize; 3] = [1, 10, 100];
+
+/// Share of the total voting power held by the `n` keys with the most voting power
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TopShare {
+    pub n: usize,
+    pub share: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DistributionMetrics {
+    pub voters: usize,
+    pub total_voting_power: Value,
+    /// 0 for a perfectly equal distribution, approaching 1 when a single key holds
+    /// all the voting power
+    pub gini: f64,
+    /// Minimum number of keys that together hold more than half of the voting power
+    pub nakamoto: usize,
+    pub top_shares: Vec<TopShare>,
+}
+
+impl DistributionMetrics {
+    pub fn from_voting_power(voting_power: impl IntoIterator<Item = u64>, top_n: &[usize]) -> Self {
+        let mut vps = voting_power.into_iter().collect::<Vec<_>>();
+        vps.sort_unstable();
+        let total = vps.iter().map(|vp| *vp as u128).sum::<u128>();
+
+        // with values sorted in non-decreasing order and 1-based index i:
+        // G = 2 * sum(i * x_i) / (n * sum(x_i)) - (n + 1) / n
+        let gini = if total == 0 {
+            0.0
+        } else {
+            let n = vps.len() as f64;
+            let weighted = vps
+                .iter()
+                .enumerate()
+                .map(|(i, vp)| (i as u128 + 1) * *vp as u128)
+                .sum::<u128>();
+            2.0 * weighted as f64 / (n * total as f64) - (n + 1.0) / n
+        };
+
+        let mut nakamoto = 0;
+        let mut held = 0u128;
+        for vp in vps.iter().rev() {
+            if total == 0 || 2 * held > total {
+                break;
+            }
+            held += *vp as u128;
+            nakamoto += 1;
+        }
+
+        let top_shares = top_n
+            .iter()
+            .map(|&n| {
+                let top = vps.iter().rev().take(n).map(|vp| *vp as u128).sum::<u128>();
+                TopShare {
+                    n,
+                    share: if total == 0 {
+                        0.0
+                    } else {
+                        top as f64 / total as f64
+                    },
+                }
+            })
+            .collect();
+
+        Self {
+            voters: vps.len(),
+            // the total voting power in a snapshot always fits into an u64
+            total_voting_power: (total as u64).into(),
+            gini,
+            nakamoto,
+            top_shares,
+        }
+    }
+}
+
+/// Metrics of a set of voters before and after the voting power cap
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CapMetrics {
+    pub before_cap: DistributionMetrics,
+    pub after_cap: DistributionMetrics,
+    /// Number of keys whose voting power was reduced by the cap
+    pub keys_affected_by_cap: usize,
+}
+
+impl CapMetrics {
+    pub fn new<'a>(
+        voters: impl IntoIterator<Item = &'a SnapshotInfo>,
+        top_n: &[usize],
+    ) -> Result<Self, Error> {
+        let mut pre_cap = Vec::new();
+        let mut capped = Vec::new();
+        let mut keys_affected_by_cap = 0;
+        for voter in voters {
+            if !voter.has_cap_info() {
+                return Err(Error::MissingCapInfo(voter.hir.voting_key.to_hex()));
+            }
+            pre_cap.push(u64::from(voter.pre_cap_voting_power));
+            capped.push(u64::from(voter.hir.voting_power));
+            if voter.cap_reduction > 0.into() {
+                keys_affected_by_cap += 1;
+            }
+        }
+        Ok(Self {
+            keys_affected_by_cap,
+            before_cap: DistributionMetrics::from_voting_power(pre_cap, top_n),
+            after_cap: DistributionMetrics::from_voting_power(capped, top_n),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotMetrics {
+    #[serde(flatten)]
+    pub overall: CapMetrics,
+    pub voting_groups: BTreeMap<VotingGroup, CapMetrics>,
+}
+
+impl SnapshotMetrics {
+    /// Fails on snapshots which don't record the voting power before the cap,
+    /// see [`SnapshotInfo::has_cap_info`]
+    pub fn new(voters: &[SnapshotInfo], top_n: &[usize]) -> Result<Self, Error> {
+        let mut groups = BTreeMap::new();
+        for voter in voters {
+            groups
+                .entry(voter.hir.voting_group.clone())
+                .or_insert_with(Vec::new)
+                .push(voter);
+        }
+        Ok(Self {
+            overall: CapMetrics::new(voters, top_n)?,
+            voting_groups: groups
+                .into_iter()
+                .map(|(group, voters)| Ok((group, CapMetrics::new(voters, top_n)?)))
+                .collect::<Result<_, Error>>()?,
+        })
+    }
+}
+
+impl Snapshot {
+    pub fn metrics(&self, top_n: &[usize]) -> SnapshotMetrics {
+        SnapshotMetrics::new(&self.to_full_snapshot_info(), top_n)
+            .expect("snapshots always record the voting power before the cap")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voter_hir::tests::VpRange;
+    use proptest::{collection::vec, prelude::*};
+    use test_strategy::proptest;
+
+    #[test]
+    fn test_equal_distribution() {
+        let metrics = DistributionMetrics::from_voting_power([100; 10], &[1, 5]);
+        assert_eq!(metrics.gini, 0.0);
+        assert_eq!(metrics.nakamoto, 6);
+        assert_eq!(
+            metrics.top_shares,
+            vec![TopShare { n: 1, share: 0.1 }, TopShare { n: 5, share: 0.5 }]
+        );
+    }
+
+    #[test]
+    fn test_single_whale() {
+        let metrics = DistributionMetrics::from_voting_power([0, 0, 0, 1000], &[1]);
+        assert_eq!(metrics.gini, 0.75);
+        assert_eq!(metrics.nakamoto, 1);
+        assert_eq!(metrics.top_shares[0].share, 1.0);
+    }
+
+    #[test]
+    fn test_empty() {
+        let metrics = DistributionMetrics::from_voting_power([], &DEFAULT_TOP_N);
+        assert_eq!(metrics.voters, 0);
+        assert_eq!(metrics.gini, 0.0);
+        assert_eq!(metrics.nakamoto, 0);
+    }
+
+    #[proptest]
+    fn test_metrics_bounds(
+        #[strategy(vec(any_with::<SnapshotInfo>((Default::default(), VpRange::ada_distribution())), 1..100))]
+        voters: Vec<SnapshotInfo>,
+    ) {
+        let metrics = SnapshotMetrics::new(&voters, &DEFAULT_TOP_N).unwrap();
+        let after = metrics.overall.after_cap;
+        // allow for rounding errors when all keys have the same voting power
+        assert!(after.gini > -1e-9 && after.gini < 1.0);
+        assert!(after.nakamoto >= 1 && after.nakamoto <= voters.len());
+        assert!(after
+            .top_shares
+            .windows(2)
+            .all(|w| w[0].share <= w[1].share));
+        assert_eq!(metrics.overall.keys_affected_by_cap, 0);
+        assert_eq!(metrics.voting_groups.len(), 1);
+    }
+
+    fn voter(i: u8, group: &str, pre_cap: u64, capped: u64) -> SnapshotInfo {
+        SnapshotInfo {
+            contributions: Vec::new(),
+            hir: crate::VoterHIR {
+                voting_key: jormungandr_lib::crypto::account::Identifier::from_hex(&hex::encode(
+                    [i; 32],
+                ))
+                .unwrap(),
+                voting_group: group.to_string(),
+                voting_power: capped.into(),
+            },
+            // the raw voting power is larger because of other transformations
+            raw_voting_power: (pre_cap * 2).into(),
+            pre_cap_voting_power: pre_cap.into(),
+            cap_reduction: (pre_cap - capped).into(),
+        }
+    }
+
+    #[test]
+    fn test_keys_affected_by_cap() {
+        let voters = [
+            voter(0, "rep", 1000, 100),
+            voter(1, "direct", 100, 100),
+            voter(2, "direct", 50, 50),
+        ];
+        let metrics = SnapshotMetrics::new(&voters, &[1]).unwrap();
+        assert_eq!(metrics.overall.keys_affected_by_cap, 1);
+        assert_eq!(metrics.voting_groups["rep"].keys_affected_by_cap, 1);
+        assert_eq!(metrics.voting_groups["direct"].keys_affected_by_cap, 0);
+        assert_eq!(metrics.overall.before_cap.total_voting_power, 1150.into());
+        assert_eq!(metrics.overall.before_cap.nakamoto, 1);
+        assert_eq!(metrics.overall.after_cap.nakamoto, 2);
+    }
+
+    #[test]
+    fn test_missing_cap_info() {
+        let mut old = voter(0, "direct", 100, 100);
+        old.pre_cap_voting_power = 0.into();
+        old.cap_reduction = 0.into();
+        assert!(matches!(
+            SnapshotMetrics::new(&[voter(1, "direct", 100, 100), old], &[1]),
+            Err(Error::MissingCapInfo(_))
+        ));
+    }
+}
@@ -1,9 +1,11 @@
+use crate::reward_address::{self, RewardAddress};
 use jormungandr_lib::crypto::account::Identifier;
 use jormungandr_lib::interfaces::Value;
 use serde::{de::Error, Deserialize, Serialize};
 
 pub type MainnetRewardAddress = String;
 pub type MainnetStakeAddress = String;
+pub type VotingPurpose = u64;
 
 /// The voting registration/delegation format as introduced in CIP-36,
 /// which is a generalization of CIP-15, allowing to distribute
@@ -14,12 +16,22 @@ pub struct VotingRegistration {
     pub stake_public_key: MainnetStakeAddress,
     pub voting_power: Value,
     /// Shelley address discriminated for the same network this transaction is submitted to.
+    /// Valid reward addresses are bech32 encoded, while anything else (e.g. a base address)
+    /// is kept as found in the registration, see [`normalize_reward_address`].
     #[serde(deserialize_with = "serde_impl::reward_addr_from_hex")]
     pub reward_address: MainnetRewardAddress,
     pub delegations: Delegations,
     /// 0 = Catalyst, assumed 0 for old legacy registrations
     #[serde(default)]
-    pub voting_purpose: u64,
+    pub voting_purpose: VotingPurpose,
+    /// Monotonically increasing value, usually the current slot number at
+    /// the time of registration. When a stake key registers multiple times
+    /// only the registration with the highest nonce is valid.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<u64>,
+    /// Slot of the block in which the registration transaction was included
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
 }
 
 impl VotingRegistration {
@@ -30,6 +42,21 @@ impl VotingRegistration {
     pub fn is_new(&self) -> bool {
         !self.is_legacy()
     }
+
+    /// The reward address with its raw bytes, which can be encoded either in bech32 or hex
+    pub fn parsed_reward_address(&self) -> Result<RewardAddress, reward_address::Error> {
+        self.reward_address.parse()
+    }
+}
+
+/// Bech32 encoding of a reward address, either hex or bech32 encoded. Anything which is not a
+/// valid reward address is returned unchanged, so that registrations can still be read. They
+/// are always reported when building the snapshot, and only discarded if a network is expected.
+pub fn normalize_reward_address(address: String) -> MainnetRewardAddress {
+    match address.parse::<RewardAddress>() {
+        Ok(reward_address) => reward_address.to_bech32(),
+        Err(_) => address,
+    }
 }
 
 /// To allow backward compatibility and avoid requiring existing users to
@@ -170,18 +197,15 @@ mod serde_impl {
     where
         D: Deserializer<'de>,
     {
-        use bech32::ToBase32;
-        let bytes = hex::decode(String::deserialize(deserializer)?.trim_start_matches("0x"))
-            .map_err(|e| D::Error::custom(format!("invalid hex string: {}", e)))?;
-        bech32::encode("stake", &bytes.to_base32(), bech32::Variant::Bech32)
-            .map_err(|e| D::Error::custom(format!("bech32 encoding failed: {}", e)))
+        // hex encoded bytes as found on chain, or already bech32 encoded, e.g. when
+        // reading back a serialized registration
+        String::deserialize(deserializer).map(normalize_reward_address)
     }
 }
 
 #[cfg(any(test, feature = "proptest"))]
 mod tests {
     use super::*;
-    use bech32::ToBase32;
     use chain_crypto::{Ed25519, SecretKey};
     use proptest::collection::vec;
     use proptest::prelude::*;
@@ -233,12 +257,15 @@ mod tests {
         type Strategy = BoxedStrategy<VotingRegistration>;
 
         fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
-            (any::<([u8; 32], [u8; 32], Delegations)>(), 0..45_000_000u64)
-                .prop_map(|((stake_key, rewards_addr, delegations), vp)| {
+            (any::<([u8; 32], [u8; 28], Delegations)>(), 0..45_000_000u64)
+                .prop_map(|((stake_key, stake_key_hash, delegations), vp)| {
                     let stake_public_key = hex::encode(stake_key);
-                    let reward_address =
-                        bech32::encode("stake", &rewards_addr.to_base32(), bech32::Variant::Bech32)
-                            .unwrap();
+                    // mainnet reward address for a stake key hash
+                    let mut rewards_addr = vec![0xe1];
+                    rewards_addr.extend_from_slice(&stake_key_hash);
+                    let reward_address = RewardAddress::from_bytes(&rewards_addr)
+                        .unwrap()
+                        .to_bech32();
                     let voting_power: Value = vp.into();
                     VotingRegistration {
                         stake_public_key,
@@ -246,6 +273,8 @@ mod tests {
                         reward_address,
                         delegations,
                         voting_purpose: 0,
+                        nonce: None,
+                        slot: None,
                     }
                 })
                 .boxed()
@@ -306,6 +335,66 @@ mod tests {
         )
     }
 
+    #[cfg(test)]
+    #[proptest]
+    fn serde_cbor(d: Delegations) {
+        assert_eq!(
+            crate::cbor::from_slice::<Delegations>(&crate::cbor::to_vec(&d).unwrap()).unwrap(),
+            d
+        )
+    }
+
+    #[cfg(test)]
+    #[proptest]
+    fn serde_json_registration(r: VotingRegistration) {
+        assert_eq!(
+            serde_json::from_str::<VotingRegistration>(&serde_json::to_string(&r).unwrap())
+                .unwrap(),
+            r
+        )
+    }
+
+    #[cfg(test)]
+    #[proptest]
+    fn serde_cbor_registration(r: VotingRegistration) {
+        assert_eq!(
+            crate::cbor::from_slice::<VotingRegistration>(&crate::cbor::to_vec(&r).unwrap())
+                .unwrap(),
+            r
+        )
+    }
+
+    #[cfg(test)]
+    #[test]
+    fn test_reward_address_is_normalized() {
+        let registration = |reward_address: &str| {
+            serde_json::from_value::<VotingRegistration>(serde_json::json!({
+                "stake_public_key": "0x00",
+                "voting_power": 1,
+                "reward_address": reward_address,
+                "delegations": "0xa6a3c0447aeb9cc54cf6422ba32b294e5e1c3ef6d782f2acff4a70694c4d1663",
+            }))
+            .unwrap()
+            .reward_address
+        };
+        let mut stake_address = vec![0xe1];
+        stake_address.extend_from_slice(&[0xab; 28]);
+        let bech32 = RewardAddress::from_bytes(&stake_address)
+            .unwrap()
+            .to_bech32();
+        assert_eq!(
+            registration(&format!("0x{}", hex::encode(&stake_address))),
+            bech32
+        );
+        assert_eq!(registration(&bech32), bech32);
+
+        // a base address (header, payment key hash and stake key hash) is kept as it is
+        let mut base_address = vec![0x01];
+        base_address.extend_from_slice(&[0xab; 56]);
+        let base_address = format!("0x{}", hex::encode(&base_address));
+        assert_eq!(registration(&base_address), base_address);
+    }
+
     #[cfg(test)]
     #[test]
     fn test_empty_delegations_are_rejected() {
@@ -0,0 +1,265 @@
+//! Shelley reward (stake) addresses, as found in registrations.
+//!
+//! A reward address is a header byte followed by a 28 bytes stake key or script hash.
+//! The upper 4 bits of the header are the address type (`0b1110` for key hashes,
+//! `0b1111` for script hashes) and the lower 4 bits are the network id
+//! (`1` for mainnet, `0` for testnets).
+use bech32::{FromBase32, ToBase32};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+const REWARD_ADDRESS_LEN: usize = 29;
+const KEY_HASH_TYPE: u8 = 0b1110;
+const SCRIPT_HASH_TYPE: u8 = 0b1111;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Mainnet,
+    Testnet,
+}
+
+impl Network {
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Self::Mainnet),
+            0 => Some(Self::Testnet),
+            _ => None,
+        }
+    }
+
+    /// Human readable part of bech32 encoded reward addresses on this network
+    pub fn hrp(&self) -> &'static str {
+        match self {
+            Self::Mainnet => "stake",
+            Self::Testnet => "stake_test",
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mainnet => f.write_str("mainnet"),
+            Self::Testnet => f.write_str("testnet"),
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mainnet" => Ok(Self::Mainnet),
+            "testnet" => Ok(Self::Testnet),
+            _ => Err(Error::UnknownNetworkName(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum Error {
+    #[error("invalid reward address length {0}, expected 29 bytes")]
+    InvalidLength(usize),
+    #[error("header byte {0:#04x} is not the header of a reward address")]
+    NotARewardAddress(u8),
+    #[error("unknown network id {0} in reward address")]
+    UnknownNetworkId(u8),
+    #[error("unknown network {0}, expected mainnet or testnet")]
+    UnknownNetworkName(String),
+    #[error("unknown address encoding {0}, expected bech32 or hex")]
+    UnknownEncoding(String),
+    #[error("reward address is for {found}, expected {expected}")]
+    WrongNetwork { expected: Network, found: Network },
+    #[error("bech32 prefix {0} does not match the network of the reward address")]
+    PrefixMismatch(String),
+    #[error("invalid hex string: {0}")]
+    Hex(#[from] hex::FromHexError),
+    #[error("invalid bech32 string: {0}")]
+    Bech32(#[from] bech32::Error),
+}
+
+/// A Shelley reward address, retaining its raw bytes
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RewardAddress(Vec<u8>);
+
+impl RewardAddress {
+    /// Parse the raw bytes of a reward address, checking the header byte
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != REWARD_ADDRESS_LEN {
+            return Err(Error::InvalidLength(bytes.len()));
+        }
+        let header = bytes[0];
+        if !matches!(header >> 4, KEY_HASH_TYPE | SCRIPT_HASH_TYPE) {
+            return Err(Error::NotARewardAddress(header));
+        }
+        if Network::from_id(header & 0x0f).is_none() {
+            return Err(Error::UnknownNetworkId(header & 0x0f));
+        }
+        Ok(Self(bytes.to_vec()))
+    }
+
+    pub fn network(&self) -> Network {
+        Network::from_id(self.0[0] & 0x0f).expect("network id is checked on creation")
+    }
+
+    /// Returns an error if this address is not for the `expected` network
+    pub fn check_network(&self, expected: Network) -> Result<(), Error> {
+        let found = self.network();
+        if found == expected {
+            Ok(())
+        } else {
+            Err(Error::WrongNetwork { expected, found })
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Bech32 encoding, with the `stake` or `stake_test` prefix depending on the network
+    pub fn to_bech32(&self) -> String {
+        bech32::encode(
+            self.network().hrp(),
+            self.0.to_base32(),
+            bech32::Variant::Bech32,
+        )
+        .expect("reward address prefixes are valid bech32 prefixes")
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.0)
+    }
+
+    pub fn encode(&self, encoding: AddressEncoding) -> String {
+        match encoding {
+            AddressEncoding::Bech32 => self.to_bech32(),
+            AddressEncoding::Hex => self.to_hex(),
+        }
+    }
+}
+
+impl fmt::Display for RewardAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_bech32())
+    }
+}
+
+/// Parse a reward address either in bech32, in which case the prefix must match the
+/// network in the header byte, or as hex encoded bytes (optionally prefixed by `0x`)
+impl FromStr for RewardAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with(Network::Mainnet.hrp()) {
+            let (hrp, data, _variant) = bech32::decode(s)?;
+            let address = Self::from_bytes(&Vec::<u8>::from_base32(&data)?)?;
+            if hrp != address.network().hrp() {
+                return Err(Error::PrefixMismatch(hrp));
+            }
+            Ok(address)
+        } else {
+            Self::from_bytes(&hex::decode(s.trim_start_matches("0x"))?)
+        }
+    }
+}
+
+/// Encoding used when writing reward addresses
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressEncoding {
+    Bech32,
+    Hex,
+}
+
+impl FromStr for AddressEncoding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bech32" => Ok(Self::Bech32),
+            "hex" => Ok(Self::Hex),
+            _ => Err(Error::UnknownEncoding(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_strategy::proptest;
+
+    fn address(header: u8) -> Vec<u8> {
+        let mut bytes = vec![header];
+        bytes.extend_from_slice(&[0xab; 28]);
+        bytes
+    }
+
+    #[test]
+    fn test_network_from_header() {
+        let mainnet = RewardAddress::from_bytes(&address(0xe1)).unwrap();
+        assert_eq!(mainnet.network(), Network::Mainnet);
+        assert!(mainnet.to_bech32().starts_with("stake1"));
+
+        let testnet = RewardAddress::from_bytes(&address(0xe0)).unwrap();
+        assert_eq!(testnet.network(), Network::Testnet);
+        assert!(testnet.to_bech32().starts_with("stake_test1"));
+
+        let script = RewardAddress::from_bytes(&address(0xf1)).unwrap();
+        assert_eq!(script.network(), Network::Mainnet);
+    }
+
+    #[test]
+    fn test_malformed_addresses_are_rejected() {
+        assert_eq!(
+            RewardAddress::from_bytes(&address(0x01)),
+            Err(Error::NotARewardAddress(0x01))
+        );
+        assert_eq!(
+            RewardAddress::from_bytes(&address(0xe2)),
+            Err(Error::UnknownNetworkId(2))
+        );
+        assert_eq!(
+            RewardAddress::from_bytes(&address(0xe1)[..20]),
+            Err(Error::InvalidLength(20))
+        );
+    }
+
+    #[test]
+    fn test_wrong_network() {
+        let testnet = RewardAddress::from_bytes(&address(0xe0)).unwrap();
+        assert!(testnet.check_network(Network::Testnet).is_ok());
+        assert_eq!(
+            testnet.check_network(Network::Mainnet),
+            Err(Error::WrongNetwork {
+                expected: Network::Mainnet,
+                found: Network::Testnet
+            })
+        );
+    }
+
+    #[test]
+    fn test_prefix_must_match_network() {
+        let testnet_bytes = address(0xe0);
+        let mislabeled =
+            bech32::encode("stake", testnet_bytes.to_base32(), bech32::Variant::Bech32).unwrap();
+        assert!(matches!(
+            mislabeled.parse::<RewardAddress>(),
+            Err(Error::PrefixMismatch(_))
+        ));
+    }
+
+    #[proptest]
+    fn test_encodings_roundtrip(#[strategy(0..2u8)] network: u8, hash: [u8; 28]) {
+        let mut bytes = vec![0xe0 | network];
+        bytes.extend_from_slice(&hash);
+        let address = RewardAddress::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            address.to_bech32().parse::<RewardAddress>().unwrap(),
+            address
+        );
+        assert_eq!(address.to_hex().parse::<RewardAddress>().unwrap(), address);
+        assert_eq!(address.as_bytes(), &bytes[..]);
+    }
+}
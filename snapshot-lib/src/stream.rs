@@ -0,0 +1,830 @@
+//! Incremental processing of registrations, allowing to build a [`Snapshot`] while
+//! registrations are being read, without holding the whole input in memory.
+use crate::{
+    registration::{
+        Delegations, MainnetRewardAddress, MainnetStakeAddress, VotingPurpose, VotingRegistration,
+    },
+    voting_group::{DelegationKinds, VotingGroupAssigner},
+    Error, Fraction, KeyContribution, Snapshot, SnapshotOptions, SnapshotReport, UncappedSnapshot,
+};
+use jormungandr_lib::{crypto::account::Identifier, interfaces::Value};
+use serde::{
+    de::{Deserializer, SeqAccess, Visitor},
+    Deserialize, Serialize,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    io::Read,
+    num::NonZeroUsize,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+/// Registrations are sent to worker threads in batches of this size
+const BATCH_SIZE: usize = 1024;
+
+/// (nonce, slot, position in input) of a registration, the greatest one is the valid one
+type Order = (u64, Option<u64>, usize);
+
+/// Receives the [`ReportEntry`] of each registration listed in the report as soon as it is
+/// known, in no particular order. Shared among worker threads when processing registrations
+/// in parallel.
+pub type ReportSink = Arc<Mutex<dyn FnMut(ReportEntry) + Send>>;
+
+/// Why a registration is listed in the [`SnapshotReport`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportReason {
+    Superseded,
+    AfterDeadline,
+    WrongNetwork,
+    MalformedRewardAddress,
+}
+
+/// A registration listed in the [`SnapshotReport`], without its delegations
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportEntry {
+    /// Position of the registration in the input
+    pub index: usize,
+    pub reason: ReportReason,
+    pub stake_public_key: MainnetStakeAddress,
+    pub reward_address: MainnetRewardAddress,
+    pub voting_purpose: VotingPurpose,
+    pub nonce: Option<u64>,
+    pub slot: Option<u64>,
+}
+
+impl ReportEntry {
+    fn new(index: usize, reason: ReportReason, registration: &VotingRegistration) -> Self {
+        Self {
+            index,
+            reason,
+            stake_public_key: registration.stake_public_key.clone(),
+            reward_address: registration.reward_address.clone(),
+            voting_purpose: registration.voting_purpose,
+            nonce: registration.nonce,
+            slot: registration.slot,
+        }
+    }
+}
+
+/// What is kept of a valid registration: its contributions, so that they can be retracted
+/// if it's superseded, and what is needed to report it in that case
+struct Processed {
+    index: usize,
+    nonce: Option<u64>,
+    slot: Option<u64>,
+    reward_address: MainnetRewardAddress,
+    kind: DelegationKinds,
+    /// Each voting key delegated to, with its contribution if it receives any voting power
+    contributions: Vec<(Identifier, Option<KeyContribution>)>,
+}
+
+impl Processed {
+    fn order(&self) -> Option<Order> {
+        self.nonce.map(|nonce| (nonce, self.slot, self.index))
+    }
+}
+
+/// Aggregates registrations, one at a time, into the contributions to each voting key.
+///
+/// Registrations are processed as in [`Snapshot::from_raw_snapshot_with_options`], which is
+/// itself implemented in terms of this builder. Each registration is converted into its
+/// contributions as soon as it's read, and only the contributions of the registrations which
+/// are currently valid for each stake key are kept, so that memory usage grows with the number
+/// of stake keys and not with the number of registrations. Registrations listed in the report
+/// are sent to the [`ReportSink`], if any, instead of being kept.
+pub struct SnapshotBuilder {
+    stake_threshold: Value,
+    options: SnapshotOptions,
+    next_index: usize,
+    /// Valid registrations for each stake key and voting purpose: either the latest
+    /// registration with a nonce, or all registrations without a nonce if there's none
+    valid: HashMap<(MainnetStakeAddress, VotingPurpose), Vec<Processed>>,
+    report: Option<ReportSink>,
+}
+
+impl SnapshotBuilder {
+    pub fn new(stake_threshold: Value, options: SnapshotOptions) -> Self {
+        Self {
+            stake_threshold,
+            options,
+            next_index: 0,
+            valid: HashMap::new(),
+            report: None,
+        }
+    }
+
+    /// Send registrations listed in the report to `report`
+    pub fn with_report(mut self, report: ReportSink) -> Self {
+        self.report = Some(report);
+        self
+    }
+
+    /// Parse a json list of registrations from `reader`, processing them as they are read
+    pub fn from_reader<R: Read>(
+        reader: R,
+        stake_threshold: Value,
+        options: SnapshotOptions,
+        report: Option<ReportSink>,
+    ) -> Result<Self, serde_json::Error> {
+        Self::many_from_reader(reader, stake_threshold, vec![options], report)
+            .map(|mut builders| builders.pop().expect("one builder per options"))
+    }
+
+    /// Same as [`SnapshotBuilder::from_reader`], but registrations are processed by `threads`
+    /// worker threads while being parsed. The result does not depend on the number of threads.
+    pub fn from_reader_parallel<R: Read>(
+        reader: R,
+        stake_threshold: Value,
+        options: SnapshotOptions,
+        report: Option<ReportSink>,
+        threads: NonZeroUsize,
+    ) -> Result<Self, serde_json::Error> {
+        Self::many_from_reader_parallel(reader, stake_threshold, vec![options], report, threads)
+            .map(|mut builders| builders.pop().expect("one builder per options"))
+    }
+
+    /// Same as [`SnapshotBuilder::from_reader`], but building one snapshot for each of the given
+    /// options (e.g. one for each voting purpose) in a single pass over `reader`
+    pub fn many_from_reader<R: Read>(
+        reader: R,
+        stake_threshold: Value,
+        options: Vec<SnapshotOptions>,
+        report: Option<ReportSink>,
+    ) -> Result<Vec<Self>, serde_json::Error> {
+        let mut builders = new_builders(stake_threshold, &options, &report);
+        let mut next_index = 0;
+        read_registrations(reader, |registration| {
+            Self::push_to_all(&mut builders, next_index, registration);
+            next_index += 1;
+        })?;
+        for builder in &mut builders {
+            builder.next_index = next_index;
+        }
+        Ok(builders)
+    }
+
+    /// Same as [`SnapshotBuilder::many_from_reader`], with registrations processed by `threads`
+    /// worker threads as in [`SnapshotBuilder::from_reader_parallel`]
+    pub fn many_from_reader_parallel<R: Read>(
+        reader: R,
+        stake_threshold: Value,
+        options: Vec<SnapshotOptions>,
+        report: Option<ReportSink>,
+        threads: NonZeroUsize,
+    ) -> Result<Vec<Self>, serde_json::Error> {
+        // bound the number of batches waiting to be processed to limit memory usage
+        let (sender, receiver) =
+            mpsc::sync_channel::<Vec<(usize, VotingRegistration)>>(threads.get());
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads.get())
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                let mut builders = new_builders(stake_threshold, &options, &report);
+                thread::spawn(move || loop {
+                    // release the lock before processing the batch
+                    let batch = receiver.lock().expect("lock poisoned").recv();
+                    match batch {
+                        Ok(batch) => {
+                            for (index, registration) in batch {
+                                Self::push_to_all(&mut builders, index, registration);
+                            }
+                        }
+                        // the channel is closed once all registrations have been sent
+                        Err(_) => break builders,
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        let mut next_index = 0;
+        let res = read_registrations(reader, |registration| {
+            batch.push((next_index, registration));
+            next_index += 1;
+            if batch.len() == BATCH_SIZE {
+                let batch = std::mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE));
+                sender.send(batch).expect("worker threads exited early");
+            }
+        });
+        if !batch.is_empty() {
+            sender.send(batch).expect("worker threads exited early");
+        }
+        drop(sender);
+
+        let mut builders = new_builders(stake_threshold, &options, &report);
+        for worker in workers {
+            let worker_builders = worker.join().expect("worker thread panicked");
+            for (builder, other) in builders.iter_mut().zip(worker_builders) {
+                builder.merge(other);
+            }
+        }
+        for builder in &mut builders {
+            builder.next_index = next_index;
+        }
+        res.map(|_| builders)
+    }
+
+    /// Push a registration to each builder considering its voting purpose, only cloning it
+    /// if more than one builder needs it
+    fn push_to_all(builders: &mut [Self], index: usize, registration: VotingRegistration) {
+        let purpose = registration.voting_purpose;
+        let mut accepting = builders
+            .iter_mut()
+            .filter(move |builder| builder.options.voting_purposes.contains(&purpose))
+            .peekable();
+        while let Some(builder) = accepting.next() {
+            if accepting.peek().is_some() {
+                builder.push_at(index, registration.clone());
+            } else {
+                builder.push_at(index, registration);
+                break;
+            }
+        }
+    }
+
+    pub fn push(&mut self, registration: VotingRegistration) {
+        let index = self.next_index;
+        self.next_index += 1;
+        self.push_at(index, registration);
+    }
+
+    fn push_at(&mut self, index: usize, registration: VotingRegistration) {
+        if !self
+            .options
+            .voting_purposes
+            .contains(&registration.voting_purpose)
+        {
+            return;
+        }
+        match (registration.parsed_reward_address(), self.options.network) {
+            (Err(_), network) => {
+                self.add_to_report(ReportEntry::new(
+                    index,
+                    ReportReason::MalformedRewardAddress,
+                    &registration,
+                ));
+                if network.is_some() {
+                    return;
+                }
+            }
+            (Ok(address), Some(network)) if address.check_network(network).is_err() => {
+                self.add_to_report(ReportEntry::new(
+                    index,
+                    ReportReason::WrongNetwork,
+                    &registration,
+                ));
+                return;
+            }
+            (Ok(_), _) => {}
+        }
+        if let (Some(deadline), Some(slot)) =
+            (self.options.registration_deadline_slot, registration.slot)
+        {
+            if slot > deadline {
+                self.add_to_report(ReportEntry::new(
+                    index,
+                    ReportReason::AfterDeadline,
+                    &registration,
+                ));
+                return;
+            }
+        }
+        let key = (
+            registration.stake_public_key.clone(),
+            registration.voting_purpose,
+        );
+        let processed = self.process(index, registration);
+        self.keep_valid(key, processed);
+    }
+
+    fn keep_valid(&mut self, key: (MainnetStakeAddress, VotingPurpose), processed: Processed) {
+        let valid = self.valid.entry(key.clone()).or_default();
+        let latest = valid.first().and_then(Processed::order);
+        let superseded = match (processed.order(), latest) {
+            // registrations without a nonce cannot be ordered among themselves and are all
+            // retained, unless superseded by a registration with a nonce
+            (None, None) => {
+                valid.push(processed);
+                return;
+            }
+            (None, Some(_)) => vec![processed],
+            (Some(order), Some(latest)) if order < latest => vec![processed],
+            (Some(_), _) => std::mem::replace(valid, vec![processed]),
+        };
+        for processed in superseded {
+            self.add_to_report(ReportEntry {
+                index: processed.index,
+                reason: ReportReason::Superseded,
+                stake_public_key: key.0.clone(),
+                reward_address: processed.reward_address,
+                voting_purpose: key.1,
+                nonce: processed.nonce,
+                slot: processed.slot,
+            });
+        }
+    }
+
+    /// Convert a registration into its contributions to each voting key
+    fn process(&self, index: usize, registration: VotingRegistration) -> Processed {
+        let kind = DelegationKinds {
+            legacy: registration.is_legacy(),
+            cip36: registration.is_new(),
+        };
+        let VotingRegistration {
+            stake_public_key,
+            reward_address,
+            delegations,
+            voting_power,
+            nonce,
+            slot,
+            ..
+        } = registration;
+        let contribution = |value, weight| KeyContribution {
+            stake_public_key: stake_public_key.clone(),
+            reward_address: reward_address.clone(),
+            value,
+            weight,
+            effective_value: None,
+        };
+
+        // Discard registrations with 0 voting power since they don't influence
+        // snapshot anyway
+        let contributions = if voting_power < std::cmp::max(self.stake_threshold, 1.into()) {
+            Vec::new()
+        } else {
+            match &delegations {
+                Delegations::Legacy(vk) => {
+                    vec![(vk.clone(), Some(contribution(voting_power.into(), None)))]
+                }
+                Delegations::New(vks) => {
+                    let mut weights = BTreeMap::<_, u64>::new();
+                    for (vk, weight) in vks {
+                        *weights.entry(vk.clone()).or_default() += *weight as u64;
+                    }
+                    // a key delegated to multiple times in the same registration receives a
+                    // single contribution, with the sum of the values and weights of its
+                    // delegations
+                    let mut values = BTreeMap::<_, u64>::new();
+                    for (vk, value) in self.options.apportionment.split(voting_power.into(), vks) {
+                        *values.entry(vk).or_default() += value;
+                    }
+                    weights
+                        .into_iter()
+                        .map(|(vk, weight)| {
+                            let value = values.get(&vk).copied();
+                            (vk, value.map(|value| contribution(value, Some(weight))))
+                        })
+                        .collect()
+                }
+            }
+        };
+        Processed {
+            index,
+            nonce,
+            slot,
+            reward_address,
+            kind,
+            contributions,
+        }
+    }
+
+    fn add_to_report(&self, entry: ReportEntry) {
+        if let Some(report) = &self.report {
+            let mut report = report.lock().expect("lock poisoned");
+            (*report)(entry);
+        }
+    }
+
+    /// Merge registrations processed by another builder. Positions of registrations
+    /// in the input must be unique among the two builders.
+    fn merge(&mut self, other: Self) {
+        for (key, valid) in other.valid {
+            for processed in valid {
+                self.keep_valid(key.clone(), processed);
+            }
+        }
+        self.next_index = std::cmp::max(self.next_index, other.next_index);
+    }
+
+    /// Build the snapshot from the registrations pushed so far. Registrations listed in the
+    /// report are not part of the returned report, which only lists the voting keys below the
+    /// threshold of their voting group, as they have been sent to the [`ReportSink`] already.
+    pub fn build(
+        self,
+        cap: Fraction,
+        voting_group_assigner: &impl VotingGroupAssigner,
+    ) -> Result<(Snapshot, SnapshotReport), Error> {
+        let (uncapped, report) = self.build_uncapped(voting_group_assigner)?;
+        Ok((uncapped.cap(cap)?, report))
+    }
+
+    /// Same as [`SnapshotBuilder::build`], without applying the voting power cap
+    pub fn build_uncapped(
+        self,
+        voting_group_assigner: &impl VotingGroupAssigner,
+    ) -> Result<(UncappedSnapshot, SnapshotReport), Error> {
+        let mut contributions = BTreeMap::<_, Vec<_>>::new();
+        let mut delegation_kinds = BTreeMap::<_, DelegationKinds>::new();
+        for processed in self.valid.into_values().flatten() {
+            for (vk, contribution) in processed.contributions {
+                let kinds = delegation_kinds.entry(vk.clone()).or_default();
+                *kinds = kinds.merge(processed.kind);
+                if let Some(contribution) = contribution {
+                    contributions
+                        .entry(vk)
+                        .or_default()
+                        .push((processed.index, contribution));
+                }
+            }
+        }
+        // restore the order of the input, which is lost when grouping registrations
+        // by stake key or processing them in parallel
+        let contributions = contributions
+            .into_iter()
+            .map(|(vk, mut contributions)| {
+                contributions.sort_by_key(|(index, _)| *index);
+                let contributions = contributions.into_iter().map(|(_, c)| c).collect();
+                (vk, contributions)
+            })
+            .collect();
+
+        UncappedSnapshot::from_contributions(
+            contributions,
+            &delegation_kinds,
+            self.stake_threshold,
+            voting_group_assigner,
+            &self.options,
+            SnapshotReport::default(),
+        )
+    }
+}
+
+/// A sink collecting report entries in memory, for inputs which are held in memory anyway
+pub(crate) fn collecting_report() -> (ReportSink, Arc<Mutex<Vec<ReportEntry>>>) {
+    let entries = Arc::new(Mutex::new(Vec::new()));
+    let collected = Arc::clone(&entries);
+    let sink: ReportSink = Arc::new(Mutex::new(move |entry: ReportEntry| {
+        collected.lock().expect("lock poisoned").push(entry)
+    }));
+    (sink, entries)
+}
+
+fn new_builders(
+    stake_threshold: Value,
+    options: &[SnapshotOptions],
+    report: &Option<ReportSink>,
+) -> Vec<SnapshotBuilder> {
+    options
+        .iter()
+        .map(|options| {
+            let builder = SnapshotBuilder::new(stake_threshold, options.clone());
+            match report {
+                Some(report) => builder.with_report(Arc::clone(report)),
+                None => builder,
+            }
+        })
+        .collect()
+}
+
+/// Parse a json list of registrations from `reader`, calling `f` on each one as soon as it's parsed
+pub fn read_registrations<R: Read>(
+    reader: R,
+    f: impl FnMut(VotingRegistration),
+) -> Result<(), serde_json::Error> {
+    struct RegistrationsVisitor<F>(F);
+
+    impl<'de, F: FnMut(VotingRegistration)> Visitor<'de> for RegistrationsVisitor<F> {
+        type Value = ();
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a list of voting registrations")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+            while let Some(registration) = seq.next_element()? {
+                (self.0)(registration);
+            }
+            Ok(())
+        }
+    }
+
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    deserializer.deserialize_seq(RegistrationsVisitor(f))?;
+    deserializer.end()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{reward_address::Network, RawSnapshot, SnapshotOptions};
+    use proptest::{collection::vec, option, prelude::*};
+    use test_strategy::proptest;
+
+    fn assigner(_vk: &Identifier) -> String {
+        String::new()
+    }
+
+    /// Straightforward implementation of the registration rules over the whole input,
+    /// used as a reference for the builder
+    fn reference_snapshot(
+        registrations: Vec<VotingRegistration>,
+        options: &SnapshotOptions,
+    ) -> (Snapshot, SnapshotReport) {
+        let mut report = SnapshotReport::default();
+        let mut on_time = Vec::new();
+        for reg in registrations {
+            if !options.voting_purposes.contains(&reg.voting_purpose) {
+                continue;
+            }
+            let address = reg.parsed_reward_address();
+            let on_network = match (&address, options.network) {
+                (Ok(address), Some(network)) => address.check_network(network).is_ok(),
+                _ => true,
+            };
+            let after_deadline = matches!(
+                (options.registration_deadline_slot, reg.slot),
+                (Some(deadline), Some(slot)) if slot > deadline
+            );
+            if address.is_err() {
+                report.malformed_reward_address.push(reg.clone());
+                if options.network.is_some() {
+                    continue;
+                }
+            }
+            if !on_network {
+                report.wrong_network.push(reg);
+            } else if after_deadline {
+                report.after_deadline.push(reg);
+            } else {
+                on_time.push(reg);
+            }
+        }
+
+        let mut latest = HashMap::new();
+        for (i, reg) in on_time.iter().enumerate() {
+            if let Some(nonce) = reg.nonce {
+                let order = (nonce, reg.slot, i);
+                latest
+                    .entry((reg.stake_public_key.clone(), reg.voting_purpose))
+                    .and_modify(|best| *best = std::cmp::max(*best, order))
+                    .or_insert(order);
+            }
+        }
+
+        let mut contributions = BTreeMap::<_, Vec<_>>::new();
+        let mut delegation_kinds = BTreeMap::<_, DelegationKinds>::new();
+        for (i, reg) in on_time.into_iter().enumerate() {
+            let valid = match latest.get(&(reg.stake_public_key.clone(), reg.voting_purpose)) {
+                Some((_, _, latest)) => *latest == i,
+                None => true,
+            };
+            if !valid {
+                report.superseded.push(reg);
+                continue;
+            }
+            if reg.voting_power < 1.into() {
+                continue;
+            }
+            let kind = DelegationKinds {
+                legacy: reg.is_legacy(),
+                cip36: reg.is_new(),
+            };
+            let values: Vec<(Identifier, u64, Option<u64>)> = match &reg.delegations {
+                Delegations::Legacy(vk) => vec![(vk.clone(), reg.voting_power.into(), None)],
+                Delegations::New(vks) => {
+                    let split = options.apportionment.split(reg.voting_power.into(), vks);
+                    let mut keys = split.iter().map(|(vk, _)| vk.clone()).collect::<Vec<_>>();
+                    keys.sort();
+                    keys.dedup();
+                    keys.into_iter()
+                        .map(|vk| {
+                            let value = split
+                                .iter()
+                                .filter(|(other, _)| other == &vk)
+                                .map(|(_, value)| *value)
+                                .sum();
+                            let weight = vks
+                                .iter()
+                                .filter(|(other, _)| other == &vk)
+                                .map(|(_, weight)| *weight as u64)
+                                .sum();
+                            (vk, value, Some(weight))
+                        })
+                        .collect()
+                }
+            };
+            let delegated = match &reg.delegations {
+                Delegations::Legacy(vk) => vec![vk.clone()],
+                Delegations::New(vks) => vks.iter().map(|(vk, _)| vk.clone()).collect(),
+            };
+            for vk in delegated {
+                let kinds = delegation_kinds.entry(vk.clone()).or_default();
+                *kinds = kinds.merge(kind);
+            }
+            for (vk, value, weight) in values {
+                contributions.entry(vk).or_default().push(KeyContribution {
+                    stake_public_key: reg.stake_public_key.clone(),
+                    reward_address: reg.reward_address.clone(),
+                    value,
+                    weight,
+                    effective_value: None,
+                });
+            }
+        }
+
+        let (uncapped, report) = UncappedSnapshot::from_contributions(
+            contributions,
+            &delegation_kinds,
+            0.into(),
+            &assigner,
+            options,
+            report,
+        )
+        .unwrap();
+        (uncapped.cap(Fraction::from(1u64)).unwrap(), report)
+    }
+
+    #[proptest]
+    fn test_builder_matches_reference(
+        #[strategy(vec((any::<VotingRegistration>(), 0..4u8, 0..2u64, option::of(0..10u64), option::of(0..10u64)), 0..3000))]
+        registrations: Vec<(VotingRegistration, u8, u64, Option<u64>, Option<u64>)>,
+        #[strategy(1..8usize)] threads: usize,
+    ) {
+        let registrations = registrations
+            .into_iter()
+            .map(|(mut reg, stake_key, purpose, nonce, slot)| {
+                // few stake keys so that some registrations are superseded,
+                // and a reward address that can be read back from json, if valid
+                reg.stake_public_key = stake_key.to_string();
+                reg.reward_address = match stake_key {
+                    3 => "0x01".to_string(),
+                    _ => format!("e{}{}", stake_key % 2, hex::encode([stake_key; 28])),
+                };
+                reg.voting_purpose = purpose;
+                reg.nonce = nonce;
+                reg.slot = slot;
+                reg
+            })
+            .collect::<Vec<_>>();
+        let options = (0..2)
+            .map(|purpose| SnapshotOptions {
+                registration_deadline_slot: Some(7),
+                network: Some(Network::Mainnet),
+                ..SnapshotOptions::for_purposes([purpose])
+            })
+            .collect::<Vec<_>>();
+        let json = serde_json::to_vec(&registrations).unwrap();
+        let registrations = serde_json::from_slice::<Vec<VotingRegistration>>(&json).unwrap();
+        // builders only report registrations through the sink
+        let build = |builder: SnapshotBuilder, entries: &Arc<Mutex<Vec<ReportEntry>>>| {
+            let purpose = *builder.options.voting_purposes.iter().next().unwrap();
+            let (snapshot, mut report) = builder.build(Fraction::from(1u64), &assigner).unwrap();
+            let entries = entries
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|entry| entry.voting_purpose == purpose)
+                .cloned()
+                .collect();
+            report.add_entries(entries, &registrations);
+            (snapshot, report)
+        };
+
+        let (many_sequential_sink, many_sequential_entries) = collecting_report();
+        let many_sequential = SnapshotBuilder::many_from_reader(
+            &json[..],
+            0.into(),
+            options.clone(),
+            Some(many_sequential_sink),
+        )
+        .unwrap();
+        let (many_parallel_sink, many_parallel_entries) = collecting_report();
+        let many_parallel = SnapshotBuilder::many_from_reader_parallel(
+            &json[..],
+            0.into(),
+            options.clone(),
+            Some(many_parallel_sink),
+            NonZeroUsize::new(threads).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(many_sequential.len(), options.len());
+        assert_eq!(many_parallel.len(), options.len());
+
+        for ((options, many_sequential), many_parallel) in
+            options.into_iter().zip(many_sequential).zip(many_parallel)
+        {
+            let expected = reference_snapshot(registrations.clone(), &options);
+            let raw = Snapshot::from_raw_snapshot_with_options(
+                RawSnapshot::from(registrations.clone()),
+                0.into(),
+                Fraction::from(1u64),
+                &assigner,
+                &options,
+            )
+            .unwrap();
+            let (sequential_sink, sequential_entries) = collecting_report();
+            let sequential = SnapshotBuilder::from_reader(
+                &json[..],
+                0.into(),
+                options.clone(),
+                Some(sequential_sink),
+            )
+            .unwrap();
+            let (parallel_sink, parallel_entries) = collecting_report();
+            let parallel = SnapshotBuilder::from_reader_parallel(
+                &json[..],
+                0.into(),
+                options,
+                Some(parallel_sink),
+                NonZeroUsize::new(threads).unwrap(),
+            )
+            .unwrap();
+            assert_eq!(raw, expected);
+            assert_eq!(build(sequential, &sequential_entries), expected);
+            assert_eq!(build(parallel, &parallel_entries), expected);
+            assert_eq!(build(many_sequential, &many_sequential_entries), expected);
+            assert_eq!(build(many_parallel, &many_parallel_entries), expected);
+        }
+    }
+
+    /// Number of contributions held by the builder
+    fn state_size(builder: &SnapshotBuilder) -> usize {
+        builder
+            .valid
+            .values()
+            .flatten()
+            .map(|processed| processed.contributions.len())
+            .sum()
+    }
+
+    #[test]
+    fn test_memory_does_not_grow_with_input() {
+        const STAKE_KEYS: u8 = 10;
+        let registration = |stake_key: u8, nonce: Option<u64>| VotingRegistration {
+            stake_public_key: stake_key.to_string(),
+            voting_power: 100.into(),
+            reward_address: format!("e1{}", hex::encode([stake_key; 28])),
+            delegations: Delegations::New(vec![
+                (
+                    Identifier::from_hex(&hex::encode([stake_key; 32])).unwrap(),
+                    1,
+                ),
+                (Identifier::from_hex(&hex::encode([0xff; 32])).unwrap(), 1),
+            ]),
+            voting_purpose: 0,
+            nonce,
+            slot: None,
+        };
+        let reported = Arc::new(Mutex::new(0usize));
+        let counter = Arc::clone(&reported);
+        let mut builder =
+            SnapshotBuilder::new(0.into(), SnapshotOptions::default()).with_report(Arc::new(
+                Mutex::new(move |_: ReportEntry| *counter.lock().unwrap() += 1),
+            ));
+
+        // registrations without a nonce are superseded by the first one with a nonce
+        for stake_key in 0..STAKE_KEYS {
+            builder.push(registration(stake_key, None));
+            builder.push(registration(stake_key, None));
+        }
+        assert_eq!(state_size(&builder), 4 * STAKE_KEYS as usize);
+        let mut size = None;
+        for nonce in 0..100 {
+            for stake_key in 0..STAKE_KEYS {
+                builder.push(registration(stake_key, Some(nonce)));
+            }
+            assert_eq!(
+                *size.get_or_insert(state_size(&builder)),
+                state_size(&builder)
+            );
+        }
+        assert_eq!(size, Some(2 * STAKE_KEYS as usize));
+        // all but the latest registration of each stake key were superseded
+        assert_eq!(*reported.lock().unwrap(), 101 * STAKE_KEYS as usize);
+
+        let (snapshot, _) = builder.build(Fraction::from(1u64), &assigner).unwrap();
+        assert_eq!(snapshot.voting_keys().count(), STAKE_KEYS as usize + 1);
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        assert!(SnapshotBuilder::from_reader(
+            &b"[{\"invalid\": 1}]"[..],
+            0.into(),
+            SnapshotOptions::default(),
+            None,
+        )
+        .is_err());
+        assert!(SnapshotBuilder::from_reader_parallel(
+            &b"[{\"invalid\": 1}]"[..],
+            0.into(),
+            SnapshotOptions::default(),
+            None,
+            NonZeroUsize::new(2).unwrap(),
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,337 @@
+use crate::{influence_cap::cap_voting_influence, Error, Fraction, SnapshotInfo};
+use jormungandr_lib::interfaces::Value;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// A transformation of the voting power of each voter in a snapshot.
+///
+/// The voting power of each voter is found in `hir.voting_power`, while the original
+/// contributions are left untouched. Voters whose voting power is reduced to 0 are
+/// removed from the snapshot.
+pub trait VotingPowerTransform {
+    fn transform(&self, voters: Vec<SnapshotInfo>) -> Result<Vec<SnapshotInfo>, Error>;
+}
+
+/// Transformations are applied in order, each one on the output of the previous one
+impl<T: VotingPowerTransform> VotingPowerTransform for [T] {
+    fn transform(&self, voters: Vec<SnapshotInfo>) -> Result<Vec<SnapshotInfo>, Error> {
+        self.iter()
+            .try_fold(voters, |voters, transform| transform.transform(voters))
+    }
+}
+
+/// Cap the influence of each voter to a fraction of the total voting power of its voting group.
+/// See [`cap_voting_influence`] for details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InfluenceCap(pub Fraction);
+
+impl VotingPowerTransform for InfluenceCap {
+    fn transform(&self, voters: Vec<SnapshotInfo>) -> Result<Vec<SnapshotInfo>, Error> {
+        cap_voting_influence(voters, self.0)
+    }
+}
+
+/// Replace the voting power of each voter with its (integer) square root
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quadratic;
+
+impl VotingPowerTransform for Quadratic {
+    fn transform(&self, voters: Vec<SnapshotInfo>) -> Result<Vec<SnapshotInfo>, Error> {
+        Ok(map_voting_power(voters, int_sqrt))
+    }
+}
+
+/// Replace the voting power `vp` of each voter with `floor(scale * log2(1 + vp))`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Logarithmic {
+    pub scale: u64,
+}
+
+impl VotingPowerTransform for Logarithmic {
+    fn transform(&self, voters: Vec<SnapshotInfo>) -> Result<Vec<SnapshotInfo>, Error> {
+        // log2(1 + vp) is at most 64, scale can't be larger than u64::MAX / 64
+        // for the result to fit into an u64
+        if self.scale > u64::MAX / 64 {
+            return Err(Error::Overflow);
+        }
+        let scale = u128::from(self.scale);
+        Ok(map_voting_power(voters, |vp| {
+            let (int, frac) = fixed_log2(u128::from(vp) + 1);
+            let scaled = scale * u128::from(int) + ((scale * u128::from(frac)) >> 64);
+            // the fractional part is 0 when int is 64, the result is at most scale * 64
+            scaled as u64
+        }))
+    }
+}
+
+/// Limit the voting power of each voter to an absolute amount
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ceiling(pub Value);
+
+impl VotingPowerTransform for Ceiling {
+    fn transform(&self, voters: Vec<SnapshotInfo>) -> Result<Vec<SnapshotInfo>, Error> {
+        let ceiling = u64::from(self.0);
+        Ok(map_voting_power(voters, |vp| std::cmp::min(vp, ceiling)))
+    }
+}
+
+/// Any of the transformations provided by this crate, in a form that
+/// can be selected from configuration or the command line.
+///
+/// The textual representation is one of:
+/// * `cap:<fraction>`, e.g. `cap:1/100`
+/// * `quadratic`
+/// * `log` or `log:<scale>`, scale defaults to 1
+/// * `ceiling:<voting power>`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transform {
+    InfluenceCap(InfluenceCap),
+    Quadratic(Quadratic),
+    Logarithmic(Logarithmic),
+    Ceiling(Ceiling),
+}
+
+impl VotingPowerTransform for Transform {
+    fn transform(&self, voters: Vec<SnapshotInfo>) -> Result<Vec<SnapshotInfo>, Error> {
+        match self {
+            Self::InfluenceCap(t) => t.transform(voters),
+            Self::Quadratic(t) => t.transform(voters),
+            Self::Logarithmic(t) => t.transform(voters),
+            Self::Ceiling(t) => t.transform(voters),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ParseTransformError {
+    #[error("unknown voting power transformation: {0}")]
+    Unknown(String),
+    #[error("missing parameter for voting power transformation {0}")]
+    MissingParameter(String),
+    #[error("invalid parameter for voting power transformation {0}: {1}")]
+    InvalidParameter(String, String),
+}
+
+impl FromStr for Transform {
+    type Err = ParseTransformError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, param) = match s.split_once(':') {
+            Some((name, param)) => (name, Some(param)),
+            None => (s, None),
+        };
+        let missing = || ParseTransformError::MissingParameter(name.to_string());
+        let invalid = |e: &dyn std::fmt::Display| {
+            ParseTransformError::InvalidParameter(name.to_string(), e.to_string())
+        };
+
+        match name {
+            "cap" => Fraction::from_str(param.ok_or_else(missing)?)
+                .map(|cap| Self::InfluenceCap(InfluenceCap(cap)))
+                .map_err(|e| invalid(&e)),
+            "quadratic" => Ok(Self::Quadratic(Quadratic)),
+            "log" => param
+                .map_or(Ok(1), u64::from_str)
+                .map(|scale| Self::Logarithmic(Logarithmic { scale }))
+                .map_err(|e| invalid(&e)),
+            "ceiling" => u64::from_str(param.ok_or_else(missing)?)
+                .map(|ceiling| Self::Ceiling(Ceiling(ceiling.into())))
+                .map_err(|e| invalid(&e)),
+            _ => Err(ParseTransformError::Unknown(s.to_string())),
+        }
+    }
+}
+
+fn map_voting_power(voters: Vec<SnapshotInfo>, f: impl Fn(u64) -> u64) -> Vec<SnapshotInfo> {
+    voters
+        .into_iter()
+        .filter_map(|mut voter| {
+            let vp = f(u64::from(voter.hir.voting_power));
+            voter.hir.voting_power = vp.into();
+            if vp > 0 {
+                Some(voter)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Calculates floor(sqrt(n)) with perfect precision
+fn int_sqrt(n: u64) -> u64 {
+    // the floating point approximation can be off by one for large numbers
+    let mut root = (n as f64).sqrt() as u64;
+    while root.checked_mul(root).map_or(true, |sq| sq > n) {
+        root -= 1;
+    }
+    while (root + 1).checked_mul(root + 1).map_or(false, |sq| sq <= n) {
+        root += 1;
+    }
+    root
+}
+
+/// Calculates log2(n) for n > 0 as an integer part and 64 bits of fractional part,
+/// without floating point arithmetic. The result is exact for powers of two, and never
+/// greater than the real value otherwise.
+fn fixed_log2(n: u128) -> (u32, u64) {
+    const ONE: u128 = 1 << 63;
+    let int = 127 - n.leading_zeros();
+    // n / 2^int in [1, 2), with 63 fractional bits
+    let mut x = if int <= 63 {
+        n << (63 - int)
+    } else {
+        n >> (int - 63)
+    };
+    let mut frac = 0u64;
+    for bit in (0..64).rev() {
+        // log2(x^2) = 2 * log2(x), the integer part of the result is the next bit of log2(x)
+        x = (x * x) >> 63;
+        if x >= 2 * ONE {
+            x >>= 1;
+            frac |= 1 << bit;
+        }
+    }
+    (int, frac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voter_hir::tests::VpRange;
+    use proptest::{collection::vec, prelude::*};
+    use test_strategy::proptest;
+
+    #[proptest]
+    fn test_int_sqrt(n: u64) {
+        let root = int_sqrt(n) as u128;
+        assert!(root * root <= n as u128);
+        assert!((root + 1) * (root + 1) > n as u128);
+    }
+
+    #[proptest]
+    fn test_fixed_log2_integer_part(#[strategy(1..=u128::from(u64::MAX) + 1)] n: u128) {
+        let (int, _) = fixed_log2(n);
+        assert!(1u128 << int <= n);
+        assert!(n < 1u128 << (int + 1));
+    }
+
+    #[test]
+    fn test_logarithmic_at_powers_of_two() {
+        let log = |scale: u64, vp: u64| {
+            let voter = SnapshotInfo {
+                contributions: Vec::new(),
+                hir: crate::VoterHIR {
+                    voting_key: jormungandr_lib::crypto::account::Identifier::from_hex(
+                        &hex::encode([0; 32]),
+                    )
+                    .unwrap(),
+                    voting_group: String::new(),
+                    voting_power: vp.into(),
+                },
+                raw_voting_power: vp.into(),
+                pre_cap_voting_power: vp.into(),
+                cap_reduction: 0.into(),
+            };
+            Logarithmic { scale }
+                .transform(vec![voter])
+                .unwrap()
+                .first()
+                .map_or(0, |v| u64::from(v.hir.voting_power))
+        };
+        for k in 1..64 {
+            let power = 1u64 << k;
+            assert_eq!(log(1, power - 2), k - 1);
+            assert_eq!(log(1, power - 1), k);
+            assert_eq!(log(1, power), k);
+        }
+        assert_eq!(log(1, u64::MAX), 64);
+        assert_eq!(log(1000, 1022), 9998);
+        assert_eq!(log(1000, 1023), 10000);
+        assert_eq!(log(1000, 1024), 10001);
+        // 2^60 - 1 is rounded to 2^60 as a f64
+        assert_eq!(log(3, (1 << 60) - 2), 179);
+        assert_eq!(log(u64::MAX / 64, u64::MAX), u64::MAX / 64 * 64);
+    }
+
+    #[proptest]
+    fn test_transforms_never_increase_voting_power(
+        #[strategy(vec(any_with::<SnapshotInfo>((Default::default(), VpRange::ada_distribution())), 1..100))]
+        voters: Vec<SnapshotInfo>,
+        ceiling: u64,
+    ) {
+        let transforms = [
+            Transform::Quadratic(Quadratic),
+            Transform::Logarithmic(Logarithmic { scale: 1 }),
+            Transform::Ceiling(Ceiling(ceiling.into())),
+        ];
+        let before = voters
+            .iter()
+            .map(|v| (v.hir.voting_key.clone(), v.hir.voting_power))
+            .collect::<std::collections::HashMap<_, _>>();
+        for transform in transforms {
+            for voter in transform.transform(voters.clone()).unwrap() {
+                assert!(voter.hir.voting_power > 0.into());
+                assert!(voter.hir.voting_power <= before[&voter.hir.voting_key]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "cap:1/10".parse::<Transform>().unwrap(),
+            Transform::InfluenceCap(InfluenceCap(Fraction::new(1u64, 10u64)))
+        );
+        assert_eq!(
+            "quadratic".parse::<Transform>().unwrap(),
+            Transform::Quadratic(Quadratic)
+        );
+        assert_eq!(
+            "log".parse::<Transform>().unwrap(),
+            Transform::Logarithmic(Logarithmic { scale: 1 })
+        );
+        assert_eq!(
+            "log:1000".parse::<Transform>().unwrap(),
+            Transform::Logarithmic(Logarithmic { scale: 1000 })
+        );
+        assert_eq!(
+            "ceiling:500".parse::<Transform>().unwrap(),
+            Transform::Ceiling(Ceiling(500.into()))
+        );
+        assert!("ceiling".parse::<Transform>().is_err());
+        assert!("cubic".parse::<Transform>().is_err());
+    }
+
+    #[test]
+    fn test_chain() {
+        let voters = [100u64, 10_000, 1_000_000]
+            .into_iter()
+            .enumerate()
+            .map(|(i, vp)| SnapshotInfo {
+                contributions: Vec::new(),
+                hir: crate::VoterHIR {
+                    voting_key: jormungandr_lib::crypto::account::Identifier::from_hex(
+                        &hex::encode([i as u8; 32]),
+                    )
+                    .unwrap(),
+                    voting_group: String::new(),
+                    voting_power: vp.into(),
+                },
+                raw_voting_power: vp.into(),
+                pre_cap_voting_power: vp.into(),
+                cap_reduction: 0.into(),
+            })
+            .collect::<Vec<_>>();
+        let transforms = [
+            Transform::Quadratic(Quadratic),
+            Transform::Ceiling(Ceiling(500.into())),
+        ];
+        let vps = transforms[..]
+            .transform(voters)
+            .unwrap()
+            .into_iter()
+            .map(|v| u64::from(v.hir.voting_power))
+            .collect::<Vec<_>>();
+        assert_eq!(vps, vec![10, 100, 500]);
+    }
+}
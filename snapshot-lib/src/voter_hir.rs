@@ -25,7 +25,7 @@ pub struct VoterHIR {
     pub voting_power: Value,
 }
 
-mod serde {
+pub(crate) mod serde {
     use super::*;
     use ::serde::{de::Error, Deserializer, Serializer};
 
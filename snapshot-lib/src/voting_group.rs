@@ -1,14 +1,53 @@
 use crate::VotingGroup;
-use graphql_client::GraphQLQuery;
-use jormungandr_lib::crypto::account::Identifier;
-use std::collections::HashSet;
+use graphql_client::{GraphQLQuery, Response};
+use jormungandr_lib::{crypto::account::Identifier, interfaces::Value};
+use serde::{Deserialize, Deserializer};
+use std::{collections::HashSet, fs::File, io::Read, path::Path};
 use thiserror::Error;
 
 pub const DEFAULT_DIRECT_VOTER_GROUP: &str = "direct";
 pub const DEFAULT_REPRESENTATIVE_GROUP: &str = "rep";
 
 pub trait VotingGroupAssigner {
-    fn assign(&self, vk: &Identifier) -> VotingGroup;
+    /// Assign a voting group with additional information about the voting key
+    fn assign_voter(&self, voter: &VoterInfo) -> VotingGroup;
+
+    /// Assign a voting group knowing only the voting key, as if it had no voting power
+    /// and no registration delegating to it
+    fn assign(&self, vk: &Identifier) -> VotingGroup {
+        self.assign_voter(&VoterInfo {
+            voting_key: vk,
+            voting_power: 0.into(),
+            delegation_kinds: DelegationKinds::default(),
+        })
+    }
+}
+
+/// Information about a voting key available when assigning its voting group
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VoterInfo<'a> {
+    pub voting_key: &'a Identifier,
+    /// Voting power delegated to the key, before thresholds and transformations
+    pub voting_power: Value,
+    pub delegation_kinds: DelegationKinds,
+}
+
+/// Kinds of registrations delegating to a voting key
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DelegationKinds {
+    /// Delegated by a legacy CIP-15 registration
+    pub legacy: bool,
+    /// Delegated by a CIP-36 registration
+    pub cip36: bool,
+}
+
+impl DelegationKinds {
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            legacy: self.legacy || other.legacy,
+            cip36: self.cip36 || other.cip36,
+        }
+    }
 }
 
 pub struct RepsVotersAssigner {
@@ -21,6 +60,22 @@ pub struct RepsVotersAssigner {
 pub enum Error {
     #[error(transparent)]
     Io(#[from] reqwest::Error),
+    #[error("representatives db query failed: {0}")]
+    Query(String),
+    #[error(transparent)]
+    File(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    #[error("invalid voting key {0}")]
+    InvalidKey(String),
+    #[error("unsupported representatives file format, expected a json or csv file")]
+    UnsupportedFormat,
+    #[error(transparent)]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("unsupported voting groups config format, expected a json or yaml file")]
+    UnsupportedConfigFormat,
 }
 
 #[derive(GraphQLQuery)]
@@ -31,51 +86,130 @@ pub enum Error {
 )]
 pub struct AllReps;
 
-#[allow(dead_code)]
-fn get_all_reps(_url: impl reqwest::IntoUrl) -> Result<HashSet<Identifier>, Error> {
-    // let response: all_reps::ResponseData = reqwest::blocking::Client::new()
-    //     .post(url)
-    //     .json(&AllReps::build_query(all_reps::Variables))
-    //     .send()?
-    //     .json()?;
-
-    // Ok(response
-    //     .representatives
-    //     .iter()
-    //     .flat_map(|reps| reps.data.iter())
-    //     .flat_map(|rep| rep.attributes.as_ref())
-    //     .flat_map(|attributes| attributes.address.as_ref())
-    //     .flat_map(|addr| Identifier::from_hex(addr))
-    //     .collect())
-    todo!()
+const REPS_PAGE_SIZE: i64 = 100;
+
+/// Fetch the voting keys of all registered representatives from the GraphQL
+/// endpoint of the representatives db, going through all result pages.
+pub fn get_all_reps(url: impl reqwest::IntoUrl) -> Result<HashSet<Identifier>, Error> {
+    let url = url.into_url()?;
+    let client = reqwest::blocking::Client::new();
+    let mut reps = HashSet::new();
+    let mut page = 1;
+
+    loop {
+        let response: Response<all_reps::ResponseData> = client
+            .post(url.clone())
+            .json(&AllReps::build_query(all_reps::Variables {
+                page,
+                page_size: REPS_PAGE_SIZE,
+            }))
+            .send()?
+            .error_for_status()?
+            .json()?;
+
+        if let Some(errors) = response.errors.filter(|errors| !errors.is_empty()) {
+            return Err(Error::Query(
+                errors
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ));
+        }
+
+        let representatives = match response.data.and_then(|data| data.representatives) {
+            Some(representatives) => representatives,
+            None => break,
+        };
+
+        for address in representatives
+            .data
+            .iter()
+            .flat_map(|rep| rep.attributes.as_ref())
+            .flat_map(|attributes| attributes.address.as_ref())
+        {
+            reps.insert(parse_voting_key(address)?);
+        }
+
+        if page >= representatives.meta.pagination.page_count {
+            break;
+        }
+        page += 1;
+    }
+
+    Ok(reps)
+}
+
+/// Load representatives voting keys from a local file, either a json list of
+/// hex encoded keys or a csv file with a `voting_key` column.
+pub fn load_reps_from_file(path: impl AsRef<Path>) -> Result<HashSet<Identifier>, Error> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => load_reps_from_json(file),
+        Some("csv") => load_reps_from_csv(file),
+        _ => Err(Error::UnsupportedFormat),
+    }
+}
+
+fn load_reps_from_json(reader: impl Read) -> Result<HashSet<Identifier>, Error> {
+    serde_json::from_reader::<_, Vec<String>>(reader)?
+        .iter()
+        .map(|key| parse_voting_key(key))
+        .collect()
+}
+
+fn load_reps_from_csv(reader: impl Read) -> Result<HashSet<Identifier>, Error> {
+    #[derive(Deserialize)]
+    struct Record {
+        voting_key: String,
+    }
+
+    csv::Reader::from_reader(reader)
+        .deserialize::<Record>()
+        .map(|record| parse_voting_key(&record?.voting_key))
+        .collect()
+}
+
+/// Parse a hex encoded voting key, with or without a `0x` prefix
+pub fn parse_voting_key(key: &str) -> Result<Identifier, Error> {
+    Identifier::from_hex(key.trim().trim_start_matches("0x"))
+        .map_err(|_| Error::InvalidKey(key.to_string()))
 }
 
 impl RepsVotersAssigner {
     pub fn new(direct_voters: VotingGroup, reps: VotingGroup) -> Self {
+        Self::new_from_repsdb(direct_voters, reps, HashSet::new())
+    }
+
+    pub fn new_from_repsdb(
+        direct_voters: VotingGroup,
+        reps: VotingGroup,
+        repsdb: HashSet<Identifier>,
+    ) -> Self {
         Self {
             direct_voters,
             reps,
-            repsdb: HashSet::new(),
+            repsdb,
         }
     }
 
-    #[cfg(feature = "test-api")]
-    pub fn new_from_repsdb(
+    pub fn new_from_reps_url(
         direct_voters: VotingGroup,
         reps: VotingGroup,
-        repsdb: HashSet<Identifier>,
+        url: impl reqwest::IntoUrl,
     ) -> Result<Self, Error> {
-        Ok(Self {
+        Ok(Self::new_from_repsdb(
             direct_voters,
             reps,
-            repsdb,
-        })
+            get_all_reps(url)?,
+        ))
     }
 }
 
 impl VotingGroupAssigner for RepsVotersAssigner {
-    fn assign(&self, vk: &Identifier) -> VotingGroup {
-        if self.repsdb.contains(vk) {
+    fn assign_voter(&self, voter: &VoterInfo) -> VotingGroup {
+        if self.repsdb.contains(voter.voting_key) {
             self.reps.clone()
         } else {
             self.direct_voters.clone()
@@ -83,12 +217,274 @@ impl VotingGroupAssigner for RepsVotersAssigner {
     }
 }
 
+/// Kind of registration a [`Rule`] can match
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegistrationKind {
+    /// Keys delegated only by legacy CIP-15 registrations
+    Legacy,
+    /// Keys delegated only by CIP-36 registrations
+    Cip36,
+}
+
+/// A rule assigning voting keys to a voting group.
+/// A key matches the rule if it satisfies all the conditions present in the rule,
+/// a rule without conditions matches any key.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub group: VotingGroup,
+    /// Voting keys in hex format, optionally prefixed by `0x`
+    #[serde(default, deserialize_with = "deserialize_keys")]
+    pub keys: Option<HashSet<Identifier>>,
+    /// Minimum voting power of the key, inclusive
+    #[serde(default)]
+    pub min_voting_power: Option<Value>,
+    /// Maximum voting power of the key, exclusive
+    #[serde(default)]
+    pub max_voting_power: Option<Value>,
+    #[serde(default)]
+    pub registration: Option<RegistrationKind>,
+}
+
+impl Rule {
+    pub fn matches(&self, voter: &VoterInfo) -> bool {
+        let registration_matches = |kind: &RegistrationKind| match kind {
+            RegistrationKind::Legacy => {
+                voter.delegation_kinds.legacy && !voter.delegation_kinds.cip36
+            }
+            RegistrationKind::Cip36 => {
+                voter.delegation_kinds.cip36 && !voter.delegation_kinds.legacy
+            }
+        };
+        self.keys
+            .as_ref()
+            .map_or(true, |keys| keys.contains(voter.voting_key))
+            && self
+                .min_voting_power
+                .map_or(true, |min| voter.voting_power >= min)
+            && self
+                .max_voting_power
+                .map_or(true, |max| voter.voting_power < max)
+            && self
+                .registration
+                .as_ref()
+                .map_or(true, registration_matches)
+    }
+}
+
+fn deserialize_keys<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<HashSet<Identifier>>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|key| parse_voting_key(key).map_err(serde::de::Error::custom))
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+/// Assign voting groups according to an ordered list of rules, loaded from a
+/// json or yaml file:
+///
+/// ```yaml
+/// rules:
+///   - group: rep
+///     keys: ["0xa6a3c0447aeb9cc54cf6422ba32b294e5e1c3ef6d782f2acff4a70694c4d1663"]
+///   - group: whale
+///     min_voting_power: 10000000000
+///     registration: cip36
+/// default: direct
+/// ```
+///
+/// Each key is assigned to the group of the first matching rule, or to the default
+/// group if no rule matches.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleBasedAssigner {
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    pub default: VotingGroup,
+}
+
+impl RuleBasedAssigner {
+    /// Load rules from a json or yaml file, depending on its extension
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Ok(serde_json::from_reader(file)?),
+            Some("yaml" | "yml") => Ok(serde_yaml::from_reader(file)?),
+            _ => Err(Error::UnsupportedConfigFormat),
+        }
+    }
+}
+
+impl VotingGroupAssigner for RuleBasedAssigner {
+    fn assign_voter(&self, voter: &VoterInfo) -> VotingGroup {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(voter))
+            .map_or(&self.default, |rule| &rule.group)
+            .clone()
+    }
+}
+
 #[cfg(any(test, feature = "test-api", feature = "proptest"))]
 impl<F> VotingGroupAssigner for F
 where
     F: Fn(&Identifier) -> VotingGroup,
 {
-    fn assign(&self, vk: &Identifier) -> VotingGroup {
-        self(vk)
+    fn assign_voter(&self, voter: &VoterInfo) -> VotingGroup {
+        self(voter.voting_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_1: &str = "a6a3c0447aeb9cc54cf6422ba32b294e5e1c3ef6d782f2acff4a70694c4d1663";
+    const KEY_2: &str = "00588e8e1d18cba576a4d35758069fe94e53f638b6faf7c07b8abd2bc5c5cdee";
+
+    fn expected() -> HashSet<Identifier> {
+        [KEY_1, KEY_2]
+            .iter()
+            .map(|key| Identifier::from_hex(key).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn load_reps_json() {
+        let json = format!(r#"["0x{}", "{}"]"#, KEY_1, KEY_2);
+        assert_eq!(load_reps_from_json(json.as_bytes()).unwrap(), expected());
+    }
+
+    #[test]
+    fn load_reps_csv() {
+        let csv = format!("voting_key\n0x{}\n{}\n", KEY_1, KEY_2);
+        assert_eq!(load_reps_from_csv(csv.as_bytes()).unwrap(), expected());
+    }
+
+    #[test]
+    fn invalid_rep_key_is_rejected() {
+        assert!(load_reps_from_json(r#"["not a key"]"#.as_bytes()).is_err());
+    }
+
+    fn voter(key: &Identifier, voting_power: u64, legacy: bool, cip36: bool) -> VoterInfo {
+        VoterInfo {
+            voting_key: key,
+            voting_power: voting_power.into(),
+            delegation_kinds: DelegationKinds { legacy, cip36 },
+        }
+    }
+
+    #[test]
+    fn rules_are_applied_in_order() {
+        let config = format!(
+            r#"
+rules:
+  - group: rep
+    keys: ["0x{}"]
+  - group: whale
+    min_voting_power: 1000
+    registration: cip36
+  - group: legacy
+    registration: legacy
+    max_voting_power: 1000
+default: direct
+"#,
+            KEY_1
+        );
+        let assigner: RuleBasedAssigner = serde_yaml::from_str(&config).unwrap();
+        let key_1 = Identifier::from_hex(KEY_1).unwrap();
+        let key_2 = Identifier::from_hex(KEY_2).unwrap();
+
+        // the first matching rule wins
+        assert_eq!(
+            assigner.assign_voter(&voter(&key_1, 5000, false, true)),
+            "rep"
+        );
+        assert_eq!(
+            assigner.assign_voter(&voter(&key_2, 5000, false, true)),
+            "whale"
+        );
+        // all conditions in a rule must match
+        assert_eq!(
+            assigner.assign_voter(&voter(&key_2, 5000, true, true)),
+            "direct"
+        );
+        assert_eq!(
+            assigner.assign_voter(&voter(&key_2, 999, true, false)),
+            "legacy"
+        );
+        assert_eq!(
+            assigner.assign_voter(&voter(&key_2, 1000, true, false)),
+            "direct"
+        );
+    }
+
+    #[test]
+    fn key_rules_are_evaluated_without_voter_info() {
+        let assigner: RuleBasedAssigner = serde_json::from_str(&format!(
+            r#"{{"rules": [{{"group": "rep", "keys": ["{}"]}}], "default": "direct"}}"#,
+            KEY_1
+        ))
+        .unwrap();
+        assert_eq!(
+            assigner.assign(&Identifier::from_hex(KEY_1).unwrap()),
+            "rep"
+        );
+        assert_eq!(
+            assigner.assign(&Identifier::from_hex(KEY_2).unwrap()),
+            "direct"
+        );
+    }
+
+    #[test]
+    fn voting_power_rules_without_voter_info() {
+        let assigner: RuleBasedAssigner = serde_json::from_str(
+            r#"{
+                "rules": [
+                    {"group": "whale", "min_voting_power": 1000},
+                    {"group": "small", "max_voting_power": 1000}
+                ],
+                "default": "direct"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            assigner.assign(&Identifier::from_hex(KEY_1).unwrap()),
+            "small"
+        );
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        assert!(serde_json::from_str::<RuleBasedAssigner>(r#"{"rules": []}"#).is_err());
+        assert!(serde_json::from_str::<RuleBasedAssigner>(
+            r#"{"rules": [{"group": "rep", "keys": ["not a key"]}], "default": "direct"}"#
+        )
+        .is_err());
+        assert!(serde_json::from_str::<RuleBasedAssigner>(
+            r#"{"rules": [{"group": "rep", "stake": 1}], "default": "direct"}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn reps_are_assigned_to_reps_group() {
+        let assigner = RepsVotersAssigner::new_from_repsdb(
+            DEFAULT_DIRECT_VOTER_GROUP.to_string(),
+            DEFAULT_REPRESENTATIVE_GROUP.to_string(),
+            [Identifier::from_hex(KEY_1).unwrap()].into_iter().collect(),
+        );
+        assert_eq!(
+            assigner.assign(&Identifier::from_hex(KEY_1).unwrap()),
+            DEFAULT_REPRESENTATIVE_GROUP
+        );
+        assert_eq!(
+            assigner.assign(&Identifier::from_hex(KEY_2).unwrap()),
+            DEFAULT_DIRECT_VOTER_GROUP
+        );
     }
 }